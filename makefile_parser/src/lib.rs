@@ -10,8 +10,8 @@ fn unfold_multiline(lines: &[String]) -> Vec<String> {
     let mut current = String::new();
     for l in lines {
         let trimmed = l.trim_end();
-        if trimmed.ends_with('\\') {
-            current.push_str(&trimmed[..trimmed.len() - 1]);
+        if let Some(stripped) = trimmed.strip_suffix('\\') {
+            current.push_str(stripped);
             current.push(' ');
         } else {
             current.push_str(trimmed);
@@ -0,0 +1,101 @@
+use crate::project::InitConfig;
+use crate::stm32cubemx::Toolchain;
+use crate::utils::user_config_dir;
+use anyhow::anyhow;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tracing::warn;
+
+/// 用户配置文件名，位于用户配置目录下
+const USER_CONFIG_FILE: &str = "config.toml";
+
+/// STM32CubeMX 相关配置
+#[derive(Debug, Default, Deserialize)]
+pub struct CubeMxConfig {
+    /// STM32CubeMX 安装目录
+    pub dir: Option<PathBuf>,
+    /// 固件包仓库目录，默认为 `~/STM32Cube/Repository`
+    pub repository: Option<PathBuf>,
+}
+
+/// 一组默认选项，优先级低于项目配置和命令行参数
+#[derive(Debug, Default, Deserialize)]
+pub struct Profile {
+    /// `create` 使用的工具链
+    pub toolchain: Option<Toolchain>,
+    /// `create` 使用的板级配置
+    pub board: Option<String>,
+    /// `init` 的选项，与项目配置中的 `[init]` 相同
+    #[serde(flatten)]
+    pub init: InitConfig,
+    /// 代替内置模板的 .clang-format 文件
+    pub clang_format: Option<PathBuf>,
+    /// 模板中的作者，默认为 `git config user.name`
+    pub author: Option<String>,
+    /// 模板中的邮箱，默认为 `git config user.email`
+    pub email: Option<String>,
+}
+
+/// 用户配置 `<用户配置目录>/config.toml`
+#[derive(Debug, Default, Deserialize)]
+pub struct UserConfig {
+    #[serde(default)]
+    pub cubemx: CubeMxConfig,
+    /// 未指定 `--profile` 时使用的 profile
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl UserConfig {
+    /// 读取用户配置，文件不存在或格式错误时使用默认值
+    pub fn load() -> UserConfig {
+        let Some(path) = user_config_dir().map(|d| d.join(USER_CONFIG_FILE)) else {
+            return UserConfig::default();
+        };
+        let Ok(content) = fs::read_to_string(&path) else {
+            return UserConfig::default();
+        };
+        toml::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignore invalid {}: {}", path.display(), e);
+            UserConfig::default()
+        })
+    }
+}
+
+static PROFILE: OnceLock<Profile> = OnceLock::new();
+
+/// 选择 profile：`--profile` 指定的，或用户配置中的 `profile`
+pub fn set_profile(name: Option<String>) -> anyhow::Result<()> {
+    let mut config = UserConfig::load();
+    let profile = match (name, config.profile.take()) {
+        (Some(name), _) => config.profiles.remove(&name).ok_or_else(|| {
+            anyhow!(
+                "Profile {} not found, available: {}",
+                name,
+                config
+                    .profiles
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?,
+        (None, Some(name)) => config.profiles.remove(&name).unwrap_or_else(|| {
+            warn!("Default profile {} not found in user config", name);
+            Profile::default()
+        }),
+        (None, None) => Profile::default(),
+    };
+    PROFILE
+        .set(profile)
+        .map_err(|_| anyhow!("Profile is already set"))
+}
+
+/// 当前使用的 profile，未选择时所有选项为空
+pub fn profile() -> &'static Profile {
+    PROFILE.get_or_init(Profile::default)
+}
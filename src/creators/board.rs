@@ -0,0 +1,329 @@
+use crate::creators::clock::{ClockFamily, ClockSetting, solve};
+use crate::utils::user_config_dir;
+use anyhow::anyhow;
+use include_dir::{Dir, include_dir};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+static DEFAULT_BOARD_DIR: Dir = include_dir!("src/configs/boards");
+
+/// 板上的 LED 或按键
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BoardPin {
+    /// 引脚名，如 `PE3`
+    pub pin: String,
+    /// 生成代码中使用的标签，如 `LED0`
+    pub label: String,
+}
+
+/// 板级设置，芯片定义中的 `[board]` 为默认值，板级配置逐项覆盖
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct BoardSettings {
+    /// 外部高速晶振频率（Hz）
+    pub hse: Option<u64>,
+    /// 外部低速晶振频率（Hz）
+    pub lse: Option<u64>,
+    /// 目标系统时钟频率（Hz）
+    pub sysclk: Option<u64>,
+    /// HAL 时基使用的定时器，如 `TIM7`
+    pub timebase: Option<String>,
+    /// 调试接口，对应芯片定义 `[debug.modes]` 中的名称，如 `serial-wire`
+    pub debug: Option<String>,
+    #[serde(default)]
+    pub leds: Vec<BoardPin>,
+    #[serde(default)]
+    pub buttons: Vec<BoardPin>,
+}
+
+impl BoardSettings {
+    /// 用 `other` 中设置了的项覆盖当前值
+    fn overlay(&self, other: &BoardSettings) -> BoardSettings {
+        let pick = |a: &Vec<BoardPin>, b: &Vec<BoardPin>| {
+            if b.is_empty() { a.clone() } else { b.clone() }
+        };
+        BoardSettings {
+            hse: other.hse.or(self.hse),
+            lse: other.lse.or(self.lse),
+            sysclk: other.sysclk.or(self.sysclk),
+            timebase: other.timebase.clone().or_else(|| self.timebase.clone()),
+            debug: other.debug.clone().or_else(|| self.debug.clone()),
+            leds: pick(&self.leds, &other.leds),
+            buttons: pick(&self.buttons, &other.buttons),
+        }
+    }
+}
+
+/// 芯片的调试接口：CubeMX 中的 IP 名和各接口对应的模式名
+#[derive(Debug, Deserialize)]
+pub struct DebugModes {
+    /// 配置调试接口的 IP，如 F4 为 `SYS`，H7 为 `DEBUG`
+    pub ip: String,
+    /// 调试接口名称到 CubeMX 模式名的映射
+    pub modes: BTreeMap<String, String>,
+}
+
+/// 命名的板级配置，`create --board <name>` 选择
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BoardProfile {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 使用的芯片定义名
+    pub chip: String,
+    #[serde(flatten)]
+    pub settings: BoardSettings,
+}
+
+/// 渲染脚本模板时使用的板级参数
+#[derive(Debug, Serialize)]
+pub struct BoardContext {
+    pub hse_value: u64,
+    pub lse_value: u64,
+    pub sysclk: u64,
+    /// 由时钟求解得到的 `clock set` 设置
+    pub clock: Vec<ClockSetting>,
+    pub timebase: String,
+    pub debug_ip: String,
+    pub debug_mode: String,
+    pub leds: Vec<BoardPin>,
+    pub buttons: Vec<BoardPin>,
+}
+
+impl BoardContext {
+    /// 合并芯片默认值与板级配置
+    pub fn resolve(
+        chip_name: &str,
+        clock_family: ClockFamily,
+        defaults: &BoardSettings,
+        debug: &DebugModes,
+        profile: Option<&BoardProfile>,
+    ) -> anyhow::Result<BoardContext> {
+        let settings = match profile {
+            Some(profile) if !profile.chip.eq_ignore_ascii_case(chip_name) => {
+                return Err(anyhow!(
+                    "Board {} is for {}, not {}",
+                    profile.name,
+                    profile.chip,
+                    chip_name
+                ));
+            }
+            Some(profile) => defaults.overlay(&profile.settings),
+            None => defaults.clone(),
+        };
+        let missing = |field: &str| anyhow!("{} is not set for {}", field, chip_name);
+        let debug_name = settings.debug.unwrap_or_else(|| "serial-wire".to_string());
+        let debug_mode = debug.modes.get(&debug_name).ok_or_else(|| {
+            anyhow!(
+                "Debug interface {} is not supported by {}, available: {}",
+                debug_name,
+                chip_name,
+                debug.modes.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+        let hse = settings.hse.ok_or_else(|| missing("hse"))?;
+        let sysclk = settings.sysclk.ok_or_else(|| missing("sysclk"))?;
+        Ok(BoardContext {
+            hse_value: hse,
+            lse_value: settings.lse.unwrap_or(32768),
+            sysclk,
+            clock: solve(clock_family, hse, sysclk)?,
+            timebase: settings.timebase.ok_or_else(|| missing("timebase"))?,
+            debug_ip: debug.ip.clone(),
+            debug_mode: debug_mode.clone(),
+            leds: settings.leds,
+            buttons: settings.buttons,
+        })
+    }
+}
+
+/// 读取所有板级配置：内置的 `src/configs/boards` 和用户目录下的 `boards`，
+/// 同名时用户配置覆盖内置配置
+pub fn load_board_profiles() -> Vec<BoardProfile> {
+    load_from(user_config_dir().map(|d| d.join("boards")))
+}
+
+/// 读取内置板级配置和 `user_dir` 下的板级配置
+fn load_from(user_dir: Option<PathBuf>) -> Vec<BoardProfile> {
+    let mut profiles: Vec<BoardProfile> = Vec::new();
+    let mut add = |profile: BoardProfile| {
+        profiles.retain(|p| p.name != profile.name);
+        profiles.push(profile);
+    };
+
+    for file in DEFAULT_BOARD_DIR.files() {
+        if file.path().extension().and_then(|s| s.to_str()) != Some("toml") {
+            continue;
+        }
+        let parsed = file
+            .contents_utf8()
+            .ok_or_else(|| anyhow!("not UTF-8"))
+            .and_then(|content| Ok(toml::from_str(content)?));
+        match parsed {
+            Ok(profile) => add(profile),
+            Err(e) => warn!("Skip board profile {}: {}", file.path().display(), e),
+        }
+    }
+
+    if let Some(user_dir) = user_dir
+        && let Ok(entries) = fs::read_dir(&user_dir)
+    {
+        let mut paths: Vec<_> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("toml"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let parsed = fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(toml::from_str(&content)?));
+            match parsed {
+                Ok(profile) => add(profile),
+                Err(e) => warn!("Skip board profile {}: {}", path.display(), e),
+            }
+        }
+    }
+    profiles
+}
+
+/// 按名称查找板级配置
+pub fn find_board(name: &str) -> anyhow::Result<BoardProfile> {
+    let profiles = load_board_profiles();
+    let available = profiles
+        .iter()
+        .map(|p| p.name.clone())
+        .collect::<Vec<_>>()
+        .join(", ");
+    profiles
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| anyhow!("Board {} not found, available: {}", name, available))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn debug_modes() -> DebugModes {
+        DebugModes {
+            ip: "SYS".to_string(),
+            modes: BTreeMap::from([
+                ("serial-wire".to_string(), "Serial Wire".to_string()),
+                ("jtag-4-pins".to_string(), "JTAG (4 pins)".to_string()),
+            ]),
+        }
+    }
+
+    fn defaults() -> BoardSettings {
+        BoardSettings {
+            hse: Some(8_000_000),
+            sysclk: Some(168_000_000),
+            timebase: Some("TIM7".to_string()),
+            leds: vec![BoardPin {
+                pin: "PA1".to_string(),
+                label: "LED0".to_string(),
+            }],
+            ..BoardSettings::default()
+        }
+    }
+
+    fn profile(chip: &str, settings: &str) -> BoardProfile {
+        toml::from_str(&format!(
+            "name = \"test\"\nchip = \"{}\"\n{}",
+            chip, settings
+        ))
+        .unwrap()
+    }
+
+    fn resolve(profile: Option<&BoardProfile>) -> anyhow::Result<BoardContext> {
+        BoardContext::resolve(
+            "STM32F407VETx",
+            ClockFamily::Stm32f4,
+            &defaults(),
+            &debug_modes(),
+            profile,
+        )
+    }
+
+    #[test]
+    fn uses_chip_defaults() {
+        let ctx = resolve(None).unwrap();
+        assert_eq!(
+            (ctx.hse_value, ctx.lse_value, ctx.sysclk),
+            (8_000_000, 32768, 168_000_000)
+        );
+        assert_eq!(ctx.timebase, "TIM7");
+        assert_eq!(
+            (ctx.debug_ip.as_str(), ctx.debug_mode.as_str()),
+            ("SYS", "Serial Wire")
+        );
+        assert_eq!(ctx.leds[0].label, "LED0");
+        assert!(ctx.buttons.is_empty());
+        assert!(!ctx.clock.is_empty());
+    }
+
+    #[test]
+    fn board_overrides_chip_defaults() {
+        let board = profile(
+            "stm32f407vetx",
+            "hse = 25000000\ndebug = \"jtag-4-pins\"\n\
+             [[buttons]]\npin = \"PC13\"\nlabel = \"KEY\"\n",
+        );
+        let ctx = resolve(Some(&board)).unwrap();
+        assert_eq!(ctx.hse_value, 25_000_000);
+        assert_eq!(ctx.debug_mode, "JTAG (4 pins)");
+        assert_eq!(ctx.buttons[0].pin, "PC13");
+        // 板级配置未设置的项保留芯片默认值
+        assert_eq!(ctx.sysclk, 168_000_000);
+        assert_eq!(ctx.timebase, "TIM7");
+        assert_eq!(ctx.leds[0].pin, "PA1");
+        // 时钟按板级配置的 HSE 求解
+        assert_eq!(
+            ctx.clock,
+            solve(ClockFamily::Stm32f4, 25_000_000, 168_000_000).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_boards() {
+        let error = |board: &BoardProfile| resolve(Some(board)).unwrap_err().to_string();
+        assert_eq!(
+            error(&profile("STM32H723VETx", "")),
+            "Board test is for STM32H723VETx, not STM32F407VETx"
+        );
+        assert_eq!(
+            error(&profile("STM32F407VETx", "debug = \"swo\"")),
+            "Debug interface swo is not supported by STM32F407VETx, available: jtag-4-pins, serial-wire"
+        );
+    }
+
+    #[test]
+    fn user_boards_override_embedded() {
+        let dir = env::temp_dir().join(format!("stm32tool-boards-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("weact.toml"),
+            "name = \"weact-h723\"\nchip = \"STM32H723VETx\"\nhse = 8000000\n",
+        )
+        .unwrap();
+        fs::write(dir.join("broken.toml"), "name = ").unwrap();
+        fs::write(dir.join("notes.txt"), "name = \"ignored\"").unwrap();
+
+        let embedded = load_from(None);
+        let profiles = load_from(Some(dir.clone()));
+        let _ = fs::remove_dir_all(&dir);
+        let board = &embedded[0];
+        assert_eq!(
+            (board.name.as_str(), board.chip.as_str()),
+            ("weact-h723", "STM32H723VETx")
+        );
+        assert_eq!(board.settings.hse, Some(25_000_000));
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].settings.hse, Some(8_000_000));
+        assert!(profiles[0].settings.leds.is_empty());
+    }
+}
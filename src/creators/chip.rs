@@ -0,0 +1,317 @@
+use crate::creators::CreateContext;
+use crate::creators::board::{BoardSettings, DebugModes};
+use crate::creators::clock::ClockFamily;
+use crate::dry_run;
+use crate::render::render_string;
+use crate::stm32cubemx::discovery::CubeMxVersion;
+use crate::stm32cubemx::ioc::IocFile;
+use crate::stm32cubemx::script::CubeMxScript;
+use crate::stm32cubemx::{backend, run_script};
+use crate::utils::user_config_dir;
+use anyhow::{Context, anyhow};
+use include_dir::{Dir, include_dir};
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use tracing::{error, info, warn};
+
+static DEFAULT_CHIP_DIR: Dir = include_dir!("src/configs/create-script");
+
+/// 芯片定义文件名，每个芯片一个目录
+const CHIP_FILE: &str = "chip.toml";
+
+/// 对 .ioc 的修改，`value` 按脚本模板的上下文渲染
+#[derive(Debug, Deserialize)]
+#[serde(tag = "mode")]
+pub enum IocPatch {
+    /// 设置键值
+    #[serde(rename = "set")]
+    Set { key: String, value: String },
+    /// 删除所有以 `prefix` 开头的键
+    #[serde(rename = "remove")]
+    Remove { prefix: String },
+    /// 把芯片目录下另一个 .ioc 片段中的条目合并进来
+    #[serde(rename = "merge")]
+    Merge { file: String },
+}
+
+/// 创建流程的一步：运行一个 CubeMX 脚本，然后修改 .ioc
+#[derive(Debug, Deserialize)]
+pub struct Stage {
+    /// 芯片目录下的脚本模板
+    pub script: String,
+    #[serde(default)]
+    pub ioc_patches: Vec<IocPatch>,
+}
+
+/// 芯片定义所在的位置
+#[derive(Debug)]
+enum ChipSource {
+    Embedded(&'static Dir<'static>),
+    Directory(PathBuf),
+}
+
+impl ChipSource {
+    fn read(&self, name: &str) -> anyhow::Result<String> {
+        match self {
+            ChipSource::Embedded(dir) => dir
+                .get_file(dir.path().join(name))
+                .and_then(|f| f.contents_utf8())
+                .map(|s| s.to_string())
+                .ok_or_else(|| anyhow!("{} not found in {}", name, dir.path().display())),
+            ChipSource::Directory(dir) => fs::read_to_string(dir.join(name))
+                .with_context(|| format!("Failed to read {}", dir.join(name).display())),
+        }
+    }
+}
+
+/// `chip.toml` 描述的芯片
+#[derive(Debug, Deserialize)]
+pub struct ChipDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 内核，如 `Cortex-M4`
+    pub core: String,
+    /// FPU，如 `fpv4-sp-d16`，没有 FPU 时省略
+    pub fpu: Option<String>,
+    /// 需要的最低 STM32CubeMX 版本，如 `6.0.0`
+    pub min_cubemx_version: Option<String>,
+    /// 时钟树所属系列，用于计算 PLL 和总线预分频
+    pub clock_family: ClockFamily,
+    /// 板级设置的默认值
+    #[serde(default)]
+    pub board: BoardSettings,
+    pub debug: DebugModes,
+    pub stages: Vec<Stage>,
+    #[serde(skip, default = "default_source")]
+    source: ChipSource,
+}
+
+fn default_source() -> ChipSource {
+    ChipSource::Embedded(&DEFAULT_CHIP_DIR)
+}
+
+impl ChipDefinition {
+    fn parse(source: ChipSource) -> anyhow::Result<ChipDefinition> {
+        let content = source.read(CHIP_FILE)?;
+        let mut def: ChipDefinition = toml::from_str(&content)?;
+        def.source = source;
+        Ok(def)
+    }
+
+    fn apply_ioc_patch(
+        &self,
+        ioc: &mut IocFile,
+        patch: &IocPatch,
+        ctx: &CreateContext,
+    ) -> anyhow::Result<()> {
+        match patch {
+            IocPatch::Set { key, value } => ioc.set(
+                key,
+                &render_string(value, ctx)
+                    .with_context(|| format!("Invalid value for {} in {}", key, self.name))?,
+            ),
+            IocPatch::Remove { prefix } => ioc.retain(|key, _| !key.starts_with(prefix.as_str())),
+            IocPatch::Merge { file } => {
+                let fragment = IocFile::parse(&self.source.read(file)?);
+                for (key, value) in fragment.entries() {
+                    ioc.set_raw(key, value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 按顺序执行各步骤
+    pub fn create(&self, ctx: &CreateContext) -> anyhow::Result<()> {
+        info!(
+            "Creating {} ({}, FPU: {})",
+            self.name,
+            self.core,
+            self.fpu.as_deref().unwrap_or("none")
+        );
+        if let Some(required) = self
+            .min_cubemx_version
+            .as_deref()
+            .and_then(CubeMxVersion::parse)
+            && let Some(installed) = backend::current().version()
+            && installed < required
+        {
+            warn!(
+                "{} requires STM32CubeMX {} or newer, but {} is installed",
+                self.name, required, installed
+            );
+        }
+        let ioc_path = format!("{}.ioc", ctx.project_name);
+        for (idx, stage) in self.stages.iter().enumerate() {
+            let text = render_string(&self.source.read(&stage.script)?, &ctx)
+                .with_context(|| format!("Invalid template {}", stage.script))?;
+            let script = CubeMxScript::parse(&text)
+                .with_context(|| format!("Invalid script {}", stage.script))?;
+            info!("Running script {} ({})", idx + 1, stage.script);
+            if let Err(e) = run_script(&script) {
+                error!("Failed to run script {}: {}", stage.script, e);
+                return Err(anyhow!("Failed to run script {}: {}", stage.script, e));
+            }
+            if stage.ioc_patches.is_empty() {
+                continue;
+            }
+            if dry_run::enabled() && !dry_run::exists(&ioc_path) {
+                // CubeMX 没有实际运行，.ioc 还不存在
+                for patch in &stage.ioc_patches {
+                    let action = match patch {
+                        IocPatch::Set { key, value } => {
+                            let value = render_string(value, ctx)
+                                .with_context(|| format!("Invalid value for {}", key))?;
+                            format!("set {}={}", key, value)
+                        }
+                        IocPatch::Remove { prefix } => format!("remove {}*", prefix),
+                        IocPatch::Merge { file } => format!("merge {}", file),
+                    };
+                    info!("[dry-run] Would patch {}: {}", ioc_path, action);
+                }
+                continue;
+            }
+            info!("Patching .ioc file");
+            let mut ioc = IocFile::load(&ioc_path)?;
+            for patch in &stage.ioc_patches {
+                self.apply_ioc_patch(&mut ioc, patch, ctx)?;
+            }
+            ioc.save(&ioc_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// 读取所有芯片定义，用户目录中的同名定义覆盖内置定义
+pub fn load_chip_definitions() -> Vec<ChipDefinition> {
+    load_from(user_config_dir().map(|d| d.join("create-script")))
+}
+
+/// 读取内置芯片定义和 `user_dir` 下的芯片定义
+fn load_from(user_dir: Option<PathBuf>) -> Vec<ChipDefinition> {
+    let mut defs: Vec<ChipDefinition> = Vec::new();
+    let mut add = |def: ChipDefinition| {
+        defs.retain(|d| d.name != def.name);
+        defs.push(def);
+    };
+
+    for dir in DEFAULT_CHIP_DIR.dirs() {
+        match ChipDefinition::parse(ChipSource::Embedded(dir)) {
+            Ok(def) => add(def),
+            Err(e) => warn!("Skip chip definition {}: {}", dir.path().display(), e),
+        }
+    }
+
+    if let Some(user_dir) = user_dir
+        && let Ok(entries) = fs::read_dir(&user_dir)
+    {
+        let mut paths: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.join(CHIP_FILE).is_file())
+            .collect();
+        paths.sort();
+        for path in paths {
+            match ChipDefinition::parse(ChipSource::Directory(path.clone())) {
+                Ok(def) => add(def),
+                Err(e) => warn!("Skip chip definition {}: {}", path.display(), e),
+            }
+        }
+    }
+    defs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creators::STM32ProjectCreator;
+    use crate::creators::board::find_board;
+    use std::env;
+
+    fn names(defs: &[ChipDefinition]) -> Vec<&str> {
+        defs.iter().map(|d| d.name.as_str()).collect()
+    }
+
+    #[test]
+    fn loads_embedded_chips() {
+        let defs = load_from(None);
+        assert_eq!(names(&defs), ["STM32F407VETx", "STM32H723VETx"]);
+        let h7 = &defs[1];
+        assert_eq!(h7.core, "Cortex-M7");
+        assert_eq!(h7.fpu.as_deref(), Some("fpv5-d16"));
+        assert!(matches!(h7.source, ChipSource::Embedded(_)));
+        // 每一步的脚本模板都存在
+        for def in &defs {
+            for stage in &def.stages {
+                def.source.read(&stage.script).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn board_values_reach_scripts() {
+        let h7 = load_from(None).pop().unwrap();
+        let board = find_board("weact-h723").unwrap();
+        let name = "demo".to_string();
+        let ctx = CreateContext {
+            project_name: &name,
+            project_dir: &name,
+            ioc_file_path: &"demo.ioc".to_string(),
+            toolchain: "CMake",
+            generate_under_root: true,
+            board: h7.board(Some(&board)).unwrap(),
+        };
+        let script: String = h7
+            .stages
+            .iter()
+            .map(|stage| render_string(&h7.source.read(&stage.script).unwrap(), &ctx).unwrap())
+            .collect();
+        for line in [
+            "set mode SYS \"TIM23\"",
+            "set mode DEBUG \"Serial_Wire\"",
+            "set gpio parameters PE3 GPIO_Label LED",
+            "set pin PC13 GPIO_Input",
+            "set ip parameters RCC HSE_VALUE 25000000",
+            "clock set DIVM1 2",
+            "clock set DIVN1 44",
+        ] {
+            assert!(
+                script.lines().any(|l| l == line),
+                "{} not in\n{}",
+                line,
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn user_chips_override_embedded_and_skip_invalid() {
+        let dir = env::temp_dir().join(format!("stm32tool-chips-{}", std::process::id()));
+        let embedded = DEFAULT_CHIP_DIR
+            .get_file("STM32F407VETx/chip.toml")
+            .and_then(|f| f.contents_utf8())
+            .unwrap();
+        let chip = |name: &str, content: &str| {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            fs::write(dir.join(name).join(CHIP_FILE), content).unwrap();
+        };
+        chip(
+            "STM32F407VETx",
+            &embedded.replace("LQFP100", "custom board"),
+        );
+        chip("broken", "name = \"STM32F401\"\ncore = ");
+        // 没有 chip.toml 的目录被忽略
+        fs::create_dir_all(dir.join("empty")).unwrap();
+
+        let defs = load_from(Some(dir.clone()));
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(names(&defs), ["STM32H723VETx", "STM32F407VETx"]);
+        let f4 = &defs[1];
+        assert!(f4.description.ends_with("custom board"));
+        assert!(
+            matches!(&f4.source, ChipSource::Directory(path) if path.ends_with("STM32F407VETx"))
+        );
+    }
+}
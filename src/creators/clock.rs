@@ -0,0 +1,363 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+const MHZ: u64 = 1_000_000;
+
+/// PLLFRACN 的分母（13 位小数）
+const FRACN_SCALE: u64 = 8192;
+
+/// AHB 预分频可选值
+const AHB_DIVIDERS: [u64; 9] = [1, 2, 4, 8, 16, 64, 128, 256, 512];
+
+/// APB 预分频可选值
+const APB_DIVIDERS: [u64; 5] = [1, 2, 4, 8, 16];
+
+/// 时钟树结构相同的芯片系列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockFamily {
+    /// STM32F405/407/415/417
+    Stm32f4,
+    /// STM32H723/725/730/733/735
+    Stm32h7,
+}
+
+/// 一条 `clock set <name> <value>`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClockSetting {
+    pub name: &'static str,
+    pub value: u64,
+}
+
+/// 一个系列的 PLL 参数范围和总线频率上限（Hz）
+struct FamilySpec {
+    divm: RangeInclusive<u64>,
+    divn: RangeInclusive<u64>,
+    divp: &'static [u64],
+    /// 小数倍频设置的名称，不支持小数倍频时为 `None`
+    fracn: Option<&'static str>,
+    /// PLL 输入（HSE / M）范围
+    ref_range: RangeInclusive<u64>,
+    sysclk_max: u64,
+    hclk_max: u64,
+    /// 各 APB 预分频的名称和上限
+    apb: &'static [(&'static str, u64)],
+    /// PLL 相关设置的名称：时钟源、M、N、P
+    names: [&'static str; 4],
+    /// PLL 和系统时钟源选择 HSE / PLL 时的取值
+    pll_source: u64,
+    sysclk_source: u64,
+    ahb_name: &'static str,
+    ahb: &'static [u64],
+    /// 写入脚本的设置及其顺序，与内置脚本原先的 `clock set` 一致；
+    /// 未列出的设置保持 CubeMX 的默认值，求解时只使用默认值
+    script: &'static [&'static str],
+}
+
+const H7_DIVP: [u64; 65] = {
+    // DIVP1 只能为 1 或偶数
+    let mut values = [1; 65];
+    let mut i = 1;
+    while i < 65 {
+        values[i] = (i as u64) * 2;
+        i += 1;
+    }
+    values
+};
+
+impl ClockFamily {
+    fn spec(self) -> FamilySpec {
+        match self {
+            ClockFamily::Stm32f4 => FamilySpec {
+                divm: 2..=63,
+                divn: 50..=432,
+                // PLLP 保持默认的 /2
+                divp: &[2],
+                fracn: None,
+                ref_range: MHZ..=2 * MHZ,
+                sysclk_max: 168 * MHZ,
+                hclk_max: 168 * MHZ,
+                apb: &[("APB1CLKDivider", 42 * MHZ), ("APB2CLKDivider", 84 * MHZ)],
+                names: ["PLLSource", "PLLM", "PLLN", "PLLP"],
+                pll_source: 1,
+                sysclk_source: 2,
+                ahb_name: "AHBCLKDivider",
+                // AHB 保持默认的 /1
+                ahb: &[1],
+                script: &[
+                    "PLLSource",
+                    "PLLM",
+                    "PLLN",
+                    "SysClkSource",
+                    "APB1CLKDivider",
+                    "APB2CLKDivider",
+                ],
+            },
+            ClockFamily::Stm32h7 => FamilySpec {
+                divm: 1..=63,
+                divn: 4..=512,
+                divp: &H7_DIVP,
+                fracn: Some("PLLFRACN"),
+                ref_range: MHZ..=16 * MHZ,
+                sysclk_max: 550 * MHZ,
+                hclk_max: 275 * MHZ,
+                apb: &[
+                    ("D1PPRE", 137_500_000),
+                    ("D2PPRE1", 137_500_000),
+                    ("D2PPRE2", 137_500_000),
+                    ("D3PPRE", 137_500_000),
+                ],
+                names: ["PLLSource", "DIVM1", "DIVN1", "DIVP1"],
+                pll_source: 2,
+                sysclk_source: 3,
+                ahb_name: "HPRE",
+                ahb: &AHB_DIVIDERS,
+                script: &[
+                    "PLLSource",
+                    "SysClkSource",
+                    "DIVM1",
+                    "DIVN1",
+                    "DIVP1",
+                    "PLLFRACN",
+                    "HPRE",
+                    "D1PPRE",
+                    "D2PPRE1",
+                    "D2PPRE2",
+                    "D3PPRE",
+                ],
+            },
+        }
+    }
+
+    /// VCO 输出范围，H7 根据 PLL 输入频率选择宽/窄 VCO
+    fn vco_range(self, ref_clk: u64) -> RangeInclusive<u64> {
+        match self {
+            ClockFamily::Stm32f4 => 100 * MHZ..=432 * MHZ,
+            ClockFamily::Stm32h7 if ref_clk >= 2 * MHZ => 192 * MHZ..=836 * MHZ,
+            ClockFamily::Stm32h7 => 150 * MHZ..=420 * MHZ,
+        }
+    }
+}
+
+struct PllSolution {
+    m: u64,
+    n: u64,
+    frac: u64,
+    p: u64,
+}
+
+/// 找到输出恰好为 `sysclk` 的 PLL 参数。优先 PLL 输入频率更高（抖动更小），其次不用小数倍频
+fn solve_pll(family: ClockFamily, spec: &FamilySpec, hse: u64, sysclk: u64) -> Option<PllSolution> {
+    let mut best: Option<(u64, bool, PllSolution)> = None;
+    for m in spec.divm.clone() {
+        // HSE / M 需落在 PLL 输入范围内，这里允许非整数 MHz
+        if hse < spec.ref_range.start() * m || hse > spec.ref_range.end() * m {
+            continue;
+        }
+        let ref_clk = hse / m;
+        for &p in spec.divp {
+            let vco = sysclk * p;
+            if !family.vco_range(ref_clk).contains(&vco) {
+                continue;
+            }
+            // vco = hse / m * (n + frac / 8192)
+            let scaled = vco as u128 * m as u128 * FRACN_SCALE as u128;
+            if !scaled.is_multiple_of(hse as u128) {
+                continue;
+            }
+            let scaled = (scaled / hse as u128) as u64;
+            let (n, frac) = (scaled / FRACN_SCALE, scaled % FRACN_SCALE);
+            if !spec.divn.contains(&n) || (frac != 0 && spec.fracn.is_none()) {
+                continue;
+            }
+            let integer = frac == 0;
+            let better = match &best {
+                None => true,
+                Some((best_ref, best_integer, _)) => {
+                    (ref_clk, integer) > (*best_ref, *best_integer)
+                }
+            };
+            if better {
+                best = Some((ref_clk, integer, PllSolution { m, n, frac, p }));
+            }
+        }
+    }
+    best.map(|(_, _, solution)| solution)
+}
+
+/// 满足 `freq / div <= max` 的最小分频
+fn pick_divider(freq: u64, max: u64, dividers: &[u64]) -> Option<u64> {
+    dividers.iter().copied().find(|div| freq <= max * div)
+}
+
+/// 由 HSE 和目标 SYSCLK 计算 PLL 和各总线预分频，输出对应的 `clock set` 设置
+pub fn solve(family: ClockFamily, hse: u64, sysclk: u64) -> anyhow::Result<Vec<ClockSetting>> {
+    let spec = family.spec();
+    if hse == 0 {
+        return Err(anyhow!("HSE must not be 0 Hz"));
+    }
+    if sysclk > spec.sysclk_max {
+        return Err(anyhow!(
+            "SYSCLK {} Hz exceeds the maximum of {} Hz for {:?}",
+            sysclk,
+            spec.sysclk_max,
+            family
+        ));
+    }
+    let pll = solve_pll(family, &spec, hse, sysclk).ok_or_else(|| {
+        anyhow!(
+            "No PLL configuration reaches SYSCLK {} Hz from HSE {} Hz on {:?}",
+            sysclk,
+            hse,
+            family
+        )
+    })?;
+
+    let ahb = pick_divider(sysclk, spec.hclk_max, spec.ahb)
+        .ok_or_else(|| anyhow!("No AHB prescaler keeps HCLK within {} Hz", spec.hclk_max))?;
+    let hclk = sysclk / ahb;
+
+    let [source, m, n, p] = spec.names;
+    let mut settings = vec![
+        ClockSetting {
+            name: source,
+            value: spec.pll_source,
+        },
+        ClockSetting {
+            name: "SysClkSource",
+            value: spec.sysclk_source,
+        },
+        ClockSetting {
+            name: m,
+            value: pll.m,
+        },
+        ClockSetting {
+            name: n,
+            value: pll.n,
+        },
+        ClockSetting {
+            name: p,
+            value: pll.p,
+        },
+    ];
+    if let Some(name) = spec.fracn {
+        settings.push(ClockSetting {
+            name,
+            value: pll.frac,
+        });
+    }
+    settings.push(ClockSetting {
+        name: spec.ahb_name,
+        value: ahb,
+    });
+    for &(name, max) in spec.apb {
+        let div = pick_divider(hclk, max, &APB_DIVIDERS)
+            .ok_or_else(|| anyhow!("No {} keeps the bus clock within {} Hz", name, max))?;
+        settings.push(ClockSetting { name, value: div });
+    }
+    settings.retain(|s| spec.script.contains(&s.name));
+    settings.sort_by_key(|s| spec.script.iter().position(|&name| name == s.name));
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(family: ClockFamily, hse: u64, sysclk: u64) -> Vec<(&'static str, u64)> {
+        solve(family, hse, sysclk)
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.name, s.value))
+            .collect()
+    }
+
+    fn pll(family: ClockFamily, hse: u64, sysclk: u64) -> (u64, u64, u64, u64) {
+        let pll = solve_pll(family, &family.spec(), hse, sysclk).unwrap();
+        (pll.m, pll.n, pll.frac, pll.p)
+    }
+
+    #[test]
+    fn f4_8mhz_matches_original_script() {
+        assert_eq!(
+            pll(ClockFamily::Stm32f4, 8 * MHZ, 168 * MHZ),
+            (4, 168, 0, 2)
+        );
+        assert_eq!(
+            settings(ClockFamily::Stm32f4, 8 * MHZ, 168 * MHZ),
+            [
+                ("PLLSource", 1),
+                ("PLLM", 4),
+                ("PLLN", 168),
+                ("SysClkSource", 2),
+                ("APB1CLKDivider", 4),
+                ("APB2CLKDivider", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn f4_other_crystals() {
+        assert_eq!(
+            pll(ClockFamily::Stm32f4, 12 * MHZ, 168 * MHZ),
+            (6, 168, 0, 2)
+        );
+        assert_eq!(
+            pll(ClockFamily::Stm32f4, 25 * MHZ, 168 * MHZ),
+            (25, 336, 0, 2)
+        );
+        // 总线分频只取决于 SYSCLK
+        assert_eq!(
+            settings(ClockFamily::Stm32f4, 25 * MHZ, 84 * MHZ)[4..],
+            [("APB1CLKDivider", 2), ("APB2CLKDivider", 1)]
+        );
+    }
+
+    #[test]
+    fn h7_550mhz() {
+        // 内置脚本原先的小数倍频设置
+        assert_eq!(
+            settings(ClockFamily::Stm32h7, 8 * MHZ, 550 * MHZ),
+            [
+                ("PLLSource", 2),
+                ("SysClkSource", 3),
+                ("DIVM1", 1),
+                ("DIVN1", 68),
+                ("DIVP1", 1),
+                ("PLLFRACN", 6144),
+                ("HPRE", 2),
+                ("D1PPRE", 2),
+                ("D2PPRE1", 2),
+                ("D2PPRE2", 2),
+                ("D3PPRE", 2),
+            ]
+        );
+        assert_eq!(
+            pll(ClockFamily::Stm32h7, 25 * MHZ, 550 * MHZ),
+            (2, 44, 0, 1)
+        );
+        assert_eq!(
+            pll(ClockFamily::Stm32h7, 12 * MHZ, 550 * MHZ),
+            (3, 137, 4096, 1)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_targets() {
+        let error = |hse: u64, sysclk: u64| {
+            solve(ClockFamily::Stm32f4, hse, sysclk)
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(error(0, 168 * MHZ).contains("HSE must not be 0"));
+        assert!(error(8 * MHZ, 180 * MHZ).contains("exceeds the maximum"));
+        assert!(error(8 * MHZ, 100 * MHZ + 1).contains("No PLL configuration"));
+        assert!(
+            solve(ClockFamily::Stm32h7, 8 * MHZ, 600 * MHZ)
+                .unwrap_err()
+                .to_string()
+                .contains("exceeds the maximum")
+        );
+    }
+}
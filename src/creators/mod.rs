@@ -1,10 +1,12 @@
-use crate::creators::stm32f407vetx::STM32F407VETx;
-use crate::creators::stm32h723vetx::STM32H723VETx;
+use crate::creators::board::{BoardContext, BoardProfile};
+use crate::creators::chip::{ChipDefinition, load_chip_definitions};
+use crate::creators::clock::ClockFamily;
 use clap::Parser;
 use serde::Serialize;
 
-mod stm32f407vetx;
-mod stm32h723vetx;
+pub mod board;
+mod chip;
+mod clock;
 
 #[derive(Debug, Parser)]
 pub struct CreatorArgs {}
@@ -16,13 +18,78 @@ pub struct CreateContext<'a> {
     pub ioc_file_path: &'a String,
     pub toolchain: &'a str,
     pub generate_under_root: bool,
+    pub board: BoardContext,
+}
+
+/// `list mcus` 显示的芯片信息
+#[derive(Serialize)]
+pub struct McuInfo {
+    pub name: String,
+    pub description: String,
+    pub core: String,
+    pub fpu: Option<String>,
+    pub clock_family: ClockFamily,
+    pub min_cubemx_version: Option<String>,
+    /// 支持的调试接口，`--board` 配置中 `debug` 的可选值
+    pub debug_interfaces: Vec<String>,
+    /// 默认的外部高速晶振频率（Hz）
+    pub hse: Option<u64>,
+    /// 默认的系统时钟频率（Hz）
+    pub sysclk: Option<u64>,
 }
 
 pub trait STM32ProjectCreator {
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    fn info(&self) -> McuInfo;
+    /// 合并芯片默认值与板级配置
+    fn board(&self, profile: Option<&BoardProfile>) -> anyhow::Result<BoardContext>;
     fn run(&self, ctx: &CreateContext) -> anyhow::Result<()>;
 }
 
+/// 所有芯片定义：内置的 `src/configs/create-script` 和用户目录下的 `create-script`，
+/// 同名时用户定义覆盖内置定义
 pub fn all() -> Vec<Box<dyn STM32ProjectCreator>> {
-    vec![Box::new(STM32F407VETx), Box::new(STM32H723VETx)]
+    load_chip_definitions()
+        .into_iter()
+        .map(|def| Box::new(def) as Box<dyn STM32ProjectCreator>)
+        .collect()
+}
+
+impl STM32ProjectCreator for ChipDefinition {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn info(&self) -> McuInfo {
+        McuInfo {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            core: self.core.clone(),
+            fpu: self.fpu.clone(),
+            clock_family: self.clock_family,
+            min_cubemx_version: self.min_cubemx_version.clone(),
+            debug_interfaces: self.debug.modes.keys().cloned().collect(),
+            hse: self.board.hse,
+            sysclk: self.board.sysclk,
+        }
+    }
+
+    fn board(&self, profile: Option<&BoardProfile>) -> anyhow::Result<BoardContext> {
+        BoardContext::resolve(
+            &self.name,
+            self.clock_family,
+            &self.board,
+            &self.debug,
+            profile,
+        )
+    }
+
+    fn run(&self, ctx: &CreateContext) -> anyhow::Result<()> {
+        self.create(ctx)
+    }
 }
@@ -0,0 +1,311 @@
+use crate::config::UserConfig;
+use crate::creators;
+use crate::stm32cubemx::backend;
+use crate::stm32cubemx::discovery::CubeMxVersion;
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 存在警告（可选工具缺失）时的退出码
+pub const EXIT_WARNINGS: i32 = 2;
+/// 存在错误（必需的工具缺失或不可用）时的退出码
+pub const EXIT_ERRORS: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// 一项检查的结果
+struct Check {
+    level: Level,
+    name: String,
+    detail: String,
+    /// 修复建议
+    hint: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &str, detail: String) -> Check {
+        Check {
+            level: Level::Ok,
+            name: name.to_string(),
+            detail,
+            hint: None,
+        }
+    }
+
+    fn problem(level: Level, name: &str, detail: String, hint: &str) -> Check {
+        Check {
+            level,
+            name: name.to_string(),
+            detail,
+            hint: Some(hint.to_string()),
+        }
+    }
+}
+
+/// 运行 `program args` 并从输出中读取版本号，无法运行时返回 `None`
+fn tool_version(program: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    // java -version 输出到 stderr
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let version = Regex::new(r"\d+\.\d+(?:\.\d+)?").unwrap();
+    Some(
+        version
+            .find(&text)
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_else(|| "unknown version".to_string()),
+    )
+}
+
+/// 检查 PATH 中的工具
+fn check_tool(name: &str, level: Level, hint: &str) -> Check {
+    match tool_version(Path::new(name), &["--version"]) {
+        Some(version) => Check::ok(name, version),
+        None => Check::problem(level, name, "not found".to_string(), hint),
+    }
+}
+
+/// 检查 CubeMX 及其 java，使用与 `run_script` 相同的查找逻辑
+fn check_cubemx() -> Vec<Check> {
+    let installation = match backend::current().installation() {
+        None => {
+            return vec![Check::ok(
+                "STM32CubeMX",
+                "not needed, using recorded fixtures".to_string(),
+            )];
+        }
+        Some(Err(e)) => {
+            return vec![Check::problem(
+                Level::Error,
+                "STM32CubeMX",
+                e.to_string(),
+                "install STM32CubeMX, or set STM32CubeMX_dir / --cubemx to its installation directory",
+            )];
+        }
+        Some(Ok(installation)) => installation,
+    };
+
+    let mut checks = Vec::new();
+    // 芯片定义要求的最低版本中最高的一个
+    let required = creators::all()
+        .iter()
+        .filter_map(|m| m.info().min_cubemx_version)
+        .filter_map(|v| CubeMxVersion::parse(&v))
+        .max();
+    let found = format!("found via {}", installation.source);
+    checks.push(match (&installation.version, required) {
+        (Some(version), Some(required)) if *version < required => Check::problem(
+            Level::Warning,
+            "STM32CubeMX",
+            format!(
+                "{} {}, chip definitions require {}",
+                version, found, required
+            ),
+            "upgrade STM32CubeMX",
+        ),
+        (Some(version), _) => Check::ok("STM32CubeMX", format!("{} {}", version, found)),
+        (None, _) => Check::problem(
+            Level::Warning,
+            "STM32CubeMX",
+            format!("unknown version {}", found),
+            "make sure the installation directory contains its version file",
+        ),
+    });
+
+    if let Some(java) = installation.java() {
+        checks.push(match tool_version(java, &["-version"]) {
+            Some(version) => Check::ok("java", format!("{} ({})", version, java.display())),
+            None => Check::problem(
+                Level::Error,
+                "java",
+                format!("{} cannot be started", java.display()),
+                "reinstall STM32CubeMX with its bundled JRE, or install Java and add it to PATH",
+            ),
+        });
+    }
+    checks
+}
+
+/// 固件包仓库目录
+fn repository_dir() -> Option<PathBuf> {
+    UserConfig::load()
+        .cubemx
+        .repository
+        .or_else(|| dirs::home_dir().map(|home| home.join("STM32Cube").join("Repository")))
+}
+
+/// 检查已安装的 STM32Cube 固件包，以及芯片定义所需的系列是否都已安装
+fn check_firmware() -> Vec<Check> {
+    const NAME: &str = "firmware packages";
+    let Some(repository) = repository_dir() else {
+        return vec![Check::problem(
+            Level::Warning,
+            NAME,
+            "home directory not found".to_string(),
+            "set [cubemx] repository in the user config",
+        )];
+    };
+    // 目录名如 STM32Cube_FW_F4_V1.28.0
+    let pattern = Regex::new(r"^STM32Cube_FW_([A-Z0-9]+)_V(.+)$").unwrap();
+    let mut packages: Vec<(String, String)> = fs::read_dir(&repository)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| {
+                    let name = e.file_name().to_string_lossy().to_string();
+                    let captures = pattern.captures(&name)?;
+                    Some((captures[1].to_string(), captures[2].to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    packages.sort();
+
+    let installed: BTreeSet<&str> = packages.iter().map(|(s, _)| s.as_str()).collect();
+    // 芯片名如 STM32F407VETx，系列为 F4
+    let missing: BTreeSet<String> = creators::all()
+        .iter()
+        .filter_map(|m| m.name().get(5..7).map(|s| s.to_uppercase()))
+        .filter(|series| !installed.contains(series.as_str()))
+        .collect();
+
+    let mut checks = Vec::new();
+    if !packages.is_empty() {
+        let list: Vec<String> = packages
+            .iter()
+            .map(|(series, version)| format!("{} {}", series, version))
+            .collect();
+        checks.push(Check::ok(
+            NAME,
+            format!("{} ({})", list.join(", "), repository.display()),
+        ));
+    }
+    if !missing.is_empty() {
+        checks.push(Check::problem(
+            Level::Warning,
+            NAME,
+            format!(
+                "missing {} in {}",
+                missing
+                    .iter()
+                    .map(|s| format!("STM32Cube_FW_{}", s))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                repository.display()
+            ),
+            "install them in STM32CubeMX: Help > Manage embedded software packages",
+        ));
+    }
+    checks
+}
+
+/// 检查开发环境，返回退出码：0 正常，`EXIT_WARNINGS` 有警告，`EXIT_ERRORS` 有错误
+pub fn run_doctor() -> i32 {
+    let mut checks = vec![check_tool(
+        "git",
+        Level::Error,
+        "install git: https://git-scm.com/downloads",
+    )];
+    checks.extend(check_cubemx());
+    for tool in [
+        "arm-none-eabi-gcc",
+        "arm-none-eabi-size",
+        "arm-none-eabi-objcopy",
+    ] {
+        checks.push(check_tool(
+            tool,
+            Level::Warning,
+            "install the Arm GNU Toolchain and add its bin directory to PATH",
+        ));
+    }
+    checks.push(check_tool(
+        "cmake",
+        Level::Warning,
+        "install CMake 3.22 or newer",
+    ));
+    // 任意一个构建工具可用即可
+    let builders: Vec<Check> = ["ninja", "make"]
+        .iter()
+        .map(|tool| check_tool(tool, Level::Warning, ""))
+        .collect();
+    match builders.iter().find(|c| c.level == Level::Ok) {
+        Some(found) => checks.push(Check::ok(
+            "ninja/make",
+            format!("{} {}", found.name, found.detail),
+        )),
+        None => checks.push(Check::problem(
+            Level::Warning,
+            "ninja/make",
+            "neither ninja nor make found".to_string(),
+            "install Ninja (recommended) or GNU Make",
+        )),
+    }
+    checks.push(check_tool(
+        "clang-format",
+        Level::Warning,
+        "install clang-format to use the generated .clang-format",
+    ));
+    checks.extend(check_firmware());
+
+    for check in &checks {
+        let tag = match check.level {
+            Level::Ok => "ok",
+            Level::Warning => "warn",
+            Level::Error => "error",
+        };
+        println!("[{:<5}] {}: {}", tag, check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("        fix: {}", hint);
+        }
+    }
+
+    exit_code(&checks)
+}
+
+/// 按最严重的检查结果得出退出码
+fn exit_code(checks: &[Check]) -> i32 {
+    match checks.iter().map(|c| c.level).max() {
+        Some(Level::Error) => EXIT_ERRORS,
+        Some(Level::Warning) => EXIT_WARNINGS,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checks(levels: &[Level]) -> Vec<Check> {
+        levels
+            .iter()
+            .map(|&level| match level {
+                Level::Ok => Check::ok("tool", "1.0".to_string()),
+                _ => Check::problem(level, "tool", "missing".to_string(), "install it"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exit_code_follows_worst_check() {
+        assert_eq!(exit_code(&[]), 0);
+        assert_eq!(exit_code(&checks(&[Level::Ok, Level::Ok])), 0);
+        assert_eq!(
+            exit_code(&checks(&[Level::Ok, Level::Warning])),
+            EXIT_WARNINGS
+        );
+        assert_eq!(
+            exit_code(&checks(&[Level::Error, Level::Warning, Level::Ok])),
+            EXIT_ERRORS
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use similar::TextDiff;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use tracing::info;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// dry-run 时“写入”的文件、“创建”和“删除”的目录，后续读取以此为准，使计划前后一致
+///
+/// 路径统一解析为绝对路径，相对路径相对于虚拟的当前目录
+#[derive(Default)]
+struct Overlay {
+    /// `set_current_dir` 切换到的目录，dry-run 时并不真正切换
+    cwd: Option<PathBuf>,
+    files: HashMap<PathBuf, String>,
+    dirs: HashSet<PathBuf>,
+    removed: Vec<PathBuf>,
+}
+
+impl Overlay {
+    fn resolve(&self, path: &Path) -> PathBuf {
+        let path = match &self.cwd {
+            Some(cwd) => cwd.join(path),
+            None => path.to_path_buf(),
+        };
+        std::path::absolute(&path).unwrap_or(path)
+    }
+
+    /// 是否位于已“删除”的目录中，且之后没有重新写入
+    fn removed(&self, path: &Path) -> bool {
+        !self.files.contains_key(path)
+            && !self.dirs.contains(path)
+            && self.removed.iter().any(|dir| path.starts_with(dir))
+    }
+}
+
+static OVERLAY: LazyLock<Mutex<Overlay>> = LazyLock::new(Default::default);
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// 是否只打印计划，不修改磁盘、不启动外部程序
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// 打印用的路径，尽量相对于实际的当前目录
+fn display_path(path: &Path) -> String {
+    env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// 切换当前目录；dry-run 时只切换虚拟的当前目录，之后的相对路径和命令都以此为准
+pub fn set_current_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    if !enabled() {
+        return env::set_current_dir(path);
+    }
+    if !exists(path) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", path.display()),
+        ));
+    }
+    let mut overlay = OVERLAY.lock().unwrap();
+    overlay.cwd = Some(overlay.resolve(path));
+    Ok(())
+}
+
+/// 当前目录；dry-run 时为 `set_current_dir` 设置的虚拟目录
+pub fn current_dir() -> io::Result<PathBuf> {
+    if enabled()
+        && let Some(cwd) = &OVERLAY.lock().unwrap().cwd
+    {
+        return Ok(cwd.clone());
+    }
+    env::current_dir()
+}
+
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let path = path.as_ref();
+    if !enabled() {
+        return fs::read_to_string(path);
+    }
+    let overlay = OVERLAY.lock().unwrap();
+    let path = overlay.resolve(path);
+    if let Some(content) = overlay.files.get(&path) {
+        return Ok(content.clone());
+    }
+    if overlay.removed(&path) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} would be removed", display_path(&path)),
+        ));
+    }
+    fs::read_to_string(path)
+}
+
+pub fn exists<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    if !enabled() {
+        return path.exists();
+    }
+    let overlay = OVERLAY.lock().unwrap();
+    let path = overlay.resolve(path);
+    if overlay.files.contains_key(&path) || overlay.dirs.contains(&path) {
+        return true;
+    }
+    !overlay.removed(&path) && path.exists()
+}
+
+/// 写文件；dry-run 时打印与当前内容的 unified diff
+pub fn write<P: AsRef<Path>>(path: P, content: &str) -> io::Result<()> {
+    let path = path.as_ref();
+    if !enabled() {
+        return fs::write(path, content);
+    }
+    let old = read_to_string(path).ok();
+    let path = OVERLAY.lock().unwrap().resolve(path);
+    let name = display_path(&path);
+    if old.as_deref() == Some(content) {
+        info!("[dry-run] {} is unchanged", name);
+    } else {
+        info!("[dry-run] Would write {}", name);
+        let old_name = match old {
+            Some(_) => format!("a/{}", name),
+            None => "/dev/null".to_string(),
+        };
+        let old = old.unwrap_or_default();
+        print!(
+            "{}",
+            TextDiff::from_lines(old.as_str(), content)
+                .unified_diff()
+                .header(&old_name, &format!("b/{}", name))
+        );
+    }
+    OVERLAY
+        .lock()
+        .unwrap()
+        .files
+        .insert(path, content.to_string());
+    Ok(())
+}
+
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    if !enabled() {
+        return fs::create_dir_all(path);
+    }
+    if !exists(path) {
+        let mut overlay = OVERLAY.lock().unwrap();
+        let path = overlay.resolve(path);
+        info!("[dry-run] Would create directory {}", display_path(&path));
+        for dir in path.ancestors() {
+            if !overlay.dirs.contains(dir) && (overlay.removed(dir) || !dir.exists()) {
+                overlay.dirs.insert(dir.to_path_buf());
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    if !enabled() {
+        return fs::remove_dir_all(path);
+    }
+    let mut overlay = OVERLAY.lock().unwrap();
+    let path = overlay.resolve(path);
+    info!("[dry-run] Would remove directory {}", display_path(&path));
+    overlay.files.retain(|file, _| !file.starts_with(&path));
+    overlay.dirs.retain(|dir| !dir.starts_with(&path));
+    overlay.removed.push(path);
+    Ok(())
+}
+
+/// 运行命令并等待结束；dry-run 时只打印命令，视为成功
+pub fn status(command: &mut Command) -> io::Result<ExitStatus> {
+    if !enabled() {
+        return command.status();
+    }
+    let args: Vec<String> = command
+        .get_args()
+        .map(|a| {
+            let a = a.to_string_lossy();
+            if a.contains(char::is_whitespace) {
+                format!("\"{}\"", a)
+            } else {
+                a.to_string()
+            }
+        })
+        .collect();
+    let program = command.get_program().to_string_lossy();
+    match &OVERLAY.lock().unwrap().cwd {
+        // 命令在虚拟的当前目录中运行
+        Some(cwd) if command.get_current_dir().is_none() => info!(
+            "[dry-run] Would run in {}: {} {}",
+            display_path(cwd),
+            program,
+            args.join(" ")
+        ),
+        _ => info!("[dry-run] Would run: {} {}", program, args.join(" ")),
+    }
+    Ok(ExitStatus::default())
+}
@@ -1,9 +1,9 @@
+use crate::dry_run;
 use chrono::Local;
 use include_dir::{Dir, include_dir};
-use serde::Deserialize;
-use std::fs::{self, File};
-use std::io::{self, Write};
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::fs;
 use tracing::{error, warn};
 
 static DEFAULT_GITIGNORE_CONFIG_DIR: Dir = include_dir!("src/configs/gitignore");
@@ -23,6 +23,21 @@ struct SubSection {
     files: Option<Vec<String>>,
     files_disabled: Option<Vec<String>>, // 可选：关闭忽略专用
 }
+/// `list gitignore-sections` 显示的配置
+#[derive(Serialize)]
+pub struct GitignoreInfo {
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+    pub sections: Vec<GitignoreSectionInfo>,
+}
+
+#[derive(Serialize)]
+pub struct GitignoreSectionInfo {
+    pub name: String,
+    pub enabled: bool,
+}
+
 fn iter_gitignore_configs(config_dir: Option<&str>) -> Box<dyn Iterator<Item = GitignoreConfig>> {
     if let Some(dir) = config_dir {
         // 外部目录：读取文件系统
@@ -50,18 +65,48 @@ fn iter_gitignore_configs(config_dir: Option<&str>) -> Box<dyn Iterator<Item = G
     }
 }
 
-pub fn generate_gitignore(config_dir: Option<&str>, is_force: bool) -> io::Result<()> {
+/// 内置的 gitignore 配置及其中的 section
+pub fn gitignore_sections() -> Vec<GitignoreInfo> {
+    let mut infos: Vec<GitignoreInfo> = iter_gitignore_configs(None)
+        .map(|config| {
+            let mut sections: Vec<GitignoreSectionInfo> = config
+                .sections
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, sec)| GitignoreSectionInfo {
+                    name,
+                    enabled: sec.enabled,
+                })
+                .collect();
+            sections.sort_by(|a, b| a.name.cmp(&b.name));
+            GitignoreInfo {
+                name: config.name,
+                description: config.description,
+                enabled: config.enabled,
+                sections,
+            }
+        })
+        .collect();
+    infos.sort_by(|a, b| a.name.cmp(&b.name));
+    infos
+}
+
+pub fn generate_gitignore(config_dir: Option<&str>, is_force: bool) -> anyhow::Result<()> {
     const PATH: &str = ".gitignore";
 
-    if Path::new(PATH).exists() && !is_force {
+    if dry_run::exists(PATH) && !is_force {
         warn!("Skip existing {}", PATH);
         return Ok(());
     }
 
-    let mut file = File::create(PATH)?;
+    let mut file = String::new();
 
     let now = Local::now();
-    writeln!(file, "# generated on {}", now.format("%Y-%m-%d %H:%M:%S"))?;
+    writeln!(
+        &mut file,
+        "# generated on {}",
+        now.format("%Y-%m-%d %H:%M:%S")
+    )?;
 
     // 扫描所有 TOML 文件
     for config in iter_gitignore_configs(config_dir) {
@@ -100,5 +145,6 @@ pub fn generate_gitignore(config_dir: Option<&str>, is_force: bool) -> io::Resul
 
         writeln!(file)?; // 空行分隔
     }
+    dry_run::write(PATH, &file)?;
     Ok(())
 }
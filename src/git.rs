@@ -0,0 +1,54 @@
+use crate::dry_run;
+use anyhow::{Result, anyhow};
+use std::process::{Command, Stdio};
+use tracing::info;
+
+/// 静默运行 git 命令，失败时返回错误
+pub fn git(args: &[&str]) -> Result<()> {
+    let status = dry_run::status(
+        Command::new("git")
+            .args(args)
+            .stdout(Stdio::null()) // 屏蔽 stdout
+            .stderr(Stdio::null()), // 屏蔽 stderr
+    )
+    .map_err(|e| anyhow!("Failed to execute git: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "git {} failed with status: {}",
+            args.join(" "),
+            status
+        ))
+    }
+}
+
+/// 确保 .gitattributes 中包含该行
+fn ensure_gitattributes_line(line: &str) -> Result<()> {
+    const PATH: &str = ".gitattributes";
+    let content = if dry_run::exists(PATH) {
+        dry_run::read_to_string(PATH)?
+    } else {
+        String::new()
+    };
+    if content.lines().any(|l| l.trim() == line) {
+        return Ok(());
+    }
+    let mut content = content;
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(line);
+    content.push('\n');
+    dry_run::write(PATH, &content)?;
+    Ok(())
+}
+
+/// 为 .ioc 文件注册按键合并的 merge driver（`stm32tool ioc merge`）
+pub fn register_ioc_merge_driver() -> Result<()> {
+    ensure_gitattributes_line("*.ioc merge=ioc")?;
+    git(&["config", "merge.ioc.name", "STM32CubeMX .ioc merge driver"])?;
+    git(&["config", "merge.ioc.driver", "stm32tool ioc merge %O %A %B"])?;
+    info!("Registered .ioc merge driver");
+    Ok(())
+}
@@ -1,10 +1,16 @@
+use crate::dry_run;
 use crate::initializers::{FPUType, IdeInitArgs, IdeInitializer};
+use crate::patches::{DocOp, DocPatch, Patch, PatchReport};
+use crate::pipeline;
 use crate::render::render_file;
+use crate::stm32cubemx::ioc::IocFile;
+use crate::stm32cubemx::{Toolchain, find_ioc_file, get_toolchain};
 use anyhow::anyhow;
 use serde::Serialize;
+use serde_json::json;
 use std::fs;
 use std::path::Path;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Serialize)]
 struct EIDEConfigContext<'a> {
@@ -23,21 +29,42 @@ struct EIDEProjectFile<'a> {
     path: &'a String,
 }
 const EIDE_CONFIG: &str = include_str!("../templates/eide-config.tmpl");
+const EIDE_CONFIG_FILE: &str = ".eide/eide.json";
 const EIDE_WORKSPACE: &str = include_str!("../templates/eide-workspace.tmpl");
+#[allow(clippy::upper_case_acronyms)]
 pub struct EIDE;
 
 impl IdeInitializer for EIDE {
+    fn id(&self) -> &'static str {
+        "eide"
+    }
+
     fn name(&self) -> &'static str {
         "VSCode + EIDE (toolchain: Makefile)"
     }
 
-    fn init(&self, args: &IdeInitArgs, force: bool) -> anyhow::Result<()> {
-        if !Path::new("Makefile").exists() {
+    fn toolchain(&self) -> Option<Toolchain> {
+        Some(Toolchain::Makefile)
+    }
+
+    fn init(&self, args: &IdeInitArgs, force: bool) -> anyhow::Result<PatchReport> {
+        if let Some(toolchain) = find_ioc_file()
+            .and_then(IocFile::load)
+            .ok()
+            .and_then(|ioc| ioc.toolchain())
+            && toolchain != Toolchain::Makefile
+        {
+            warn!(
+                "EIDE expects the Makefile toolchain, but the project uses {}",
+                get_toolchain(&toolchain)
+            );
+        }
+        if !dry_run::exists("Makefile") {
             error!("Makefile is not exists, initialization failed");
             return Err(anyhow!("Makefile is not exists, initialization failed"));
         }
 
-        let makefile = fs::read_to_string("Makefile")?;
+        let makefile = dry_run::read_to_string("Makefile")?;
         let parsed_makefile = makefile_parser::parse_makefile(makefile.as_str());
 
         let mut files = Vec::with_capacity(parsed_makefile.asm_sources.len());
@@ -53,38 +80,54 @@ impl IdeInitializer for EIDE {
         for entry in fs::read_dir(path)? {
             let entry = entry?;
             let path = entry.path();
-            if path.is_dir() {
-                if let Some(name) = path.file_name() {
-                    if let Some(name_str) = name.to_str() {
-                        if !name_str.starts_with('.') {
-                            src.push(name_str.to_string());
-                        }
-                    }
-                }
+            if path.is_dir()
+                && let Some(name_str) = path.file_name().and_then(|name| name.to_str())
+                && !name_str.starts_with('.')
+            {
+                src.push(name_str.to_string());
             }
         }
         let mut includes = parsed_makefile.includes;
         includes.push("UserCode".to_string());
+        let ld_file_path = parsed_makefile.ldscript.unwrap_or_default();
 
         let ctx = EIDEConfigContext {
             project_name: &project_name,
-            ld_file_path: &parsed_makefile.ldscript.unwrap_or_default(),
+            ld_file_path: &ld_file_path,
             src_dirs: &serde_json::to_string(&src)?,
             include_list: &serde_json::to_string(&includes)?,
             define_list: &serde_json::to_string(&parsed_makefile.defines)?,
             src_files: &serde_json::to_string(&files)?,
-            floating_point_hardware: match args.fpu {
+            floating_point_hardware: match args.fpu() {
                 FPUType::Hard => "single",
                 FPUType::Soft => "none",
             },
-            fpu_type: match args.fpu {
+            fpu_type: match args.fpu() {
                 FPUType::Hard => "hard",
                 FPUType::Soft => "soft",
             },
         };
 
-        info!("Generating EIDE config file...");
-        render_file(".eide/eide.json", EIDE_CONFIG, &ctx, force)?;
+        let mut report = PatchReport::default();
+        if dry_run::exists(EIDE_CONFIG_FILE) && !force {
+            // 保留用户在 EIDE 中的修改，只补上 init 需要的内容
+            info!("Updating existing EIDE config file...");
+            let config = ExistingConfig {
+                src_dirs: &src,
+                includes: &includes,
+                defines: &parsed_makefile.defines,
+                files: &files,
+                ld_file_path: &ld_file_path,
+                floating_point_hardware: ctx.floating_point_hardware,
+                fpu_type: ctx.fpu_type,
+            };
+            for patch in config.patches()? {
+                report.apply(&patch)?;
+            }
+        } else {
+            info!("Generating EIDE config file...");
+            render_file(EIDE_CONFIG_FILE, EIDE_CONFIG, &ctx, force)?;
+        }
         info!("Generating EIDE workspace file...");
         render_file(
             format!("{project_name}.code-workspace").as_str(),
@@ -93,6 +136,70 @@ impl IdeInitializer for EIDE {
             force,
         )?;
 
-        Ok(())
+        report.extend(pipeline::current().apply(self.id(), args.fpu())?);
+        Ok(report)
+    }
+}
+
+/// 更新已有 `.eide/eide.json` 时使用的值
+struct ExistingConfig<'a> {
+    src_dirs: &'a [String],
+    includes: &'a [String],
+    defines: &'a [String],
+    files: &'a [EIDEProjectFile<'a>],
+    ld_file_path: &'a str,
+    floating_point_hardware: &'a str,
+    fpu_type: &'a str,
+}
+
+impl ExistingConfig<'_> {
+    /// 追加缺少的源码目录、文件、头文件路径和宏，设置链接脚本和 FPU
+    fn patches(&self) -> anyhow::Result<Vec<Patch>> {
+        let patch = |path: &[&str], op, value| {
+            Patch::Json(DocPatch {
+                file: EIDE_CONFIG_FILE.to_string(),
+                path: path.iter().map(|s| s.to_string()).collect(),
+                op,
+                value,
+            })
+        };
+        Ok(vec![
+            patch(&["srcDirs"], DocOp::Append, json!(self.src_dirs)),
+            patch(
+                &["virtualFolder", "files"],
+                DocOp::Append,
+                serde_json::to_value(self.files)?,
+            ),
+            patch(
+                &["targets", "Debug", "custom_dep", "incList"],
+                DocOp::Append,
+                json!(self.includes),
+            ),
+            patch(
+                &["targets", "Debug", "custom_dep", "defineList"],
+                DocOp::Append,
+                json!(self.defines),
+            ),
+            patch(
+                &["targets", "Debug", "compileConfig"],
+                DocOp::Merge,
+                json!({
+                    "floatingPointHardware": self.floating_point_hardware,
+                    "scatterFilePath": self.ld_file_path,
+                }),
+            ),
+            patch(
+                &[
+                    "targets",
+                    "Debug",
+                    "builderOptions",
+                    "GCC",
+                    "global",
+                    "$float-abi-type",
+                ],
+                DocOp::Set,
+                json!(self.fpu_type),
+            ),
+        ])
     }
 }
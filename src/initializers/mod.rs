@@ -1,29 +1,51 @@
 use crate::initializers::clion::CLion;
 use crate::initializers::cmake::CMake;
 use crate::initializers::eide::EIDE;
+use crate::patches::PatchReport;
+use crate::stm32cubemx::Toolchain;
+use anyhow::anyhow;
 use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
 use tracing::info;
 
 mod clion;
 mod cmake;
 mod eide;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-enum FPUType {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FPUType {
     Hard,
     Soft,
 }
 
 #[derive(Parser, Debug)]
 pub struct IdeInitArgs {
-    /// 选择 FPU 类型
-    #[arg(long, short, default_value = "hard")]
-    fpu: FPUType,
+    /// 要初始化的 IDE，可多次指定或用逗号分隔（cmake、clion、eide，none 表示不初始化），省略时交互选择
+    #[arg(long = "ide", value_delimiter = ',')]
+    pub ides: Vec<String>,
+
+    /// 选择 FPU 类型，默认使用项目配置中的选择或 hard
+    #[arg(long, short)]
+    pub fpu: Option<FPUType>,
+}
+
+impl IdeInitArgs {
+    pub fn fpu(&self) -> FPUType {
+        self.fpu.unwrap_or(FPUType::Hard)
+    }
 }
 
 pub trait IdeInitializer {
+    /// 命令行中使用的名称，如 `--ide cmake`
+    fn id(&self) -> &'static str;
     fn name(&self) -> &'static str;
-    fn init(&self, args: &IdeInitArgs, force: bool) -> anyhow::Result<()>;
+    /// 要求工程使用的 CubeMX 工具链，没有要求时为 `None`
+    fn toolchain(&self) -> Option<Toolchain> {
+        None
+    }
+    /// 返回应用的补丁的结果
+    fn init(&self, args: &IdeInitArgs, force: bool) -> anyhow::Result<PatchReport>;
 }
 
 pub fn all() -> Vec<Box<dyn IdeInitializer>> {
@@ -32,12 +54,40 @@ pub fn all() -> Vec<Box<dyn IdeInitializer>> {
 
 pub struct IdeNone;
 impl IdeInitializer for IdeNone {
+    fn id(&self) -> &'static str {
+        "none"
+    }
+
     fn name(&self) -> &'static str {
         "None"
     }
 
-    fn init(&self, _args: &IdeInitArgs, _force: bool) -> anyhow::Result<()> {
+    fn init(&self, _args: &IdeInitArgs, _force: bool) -> anyhow::Result<PatchReport> {
         info!("No IDE initializer selected");
-        Ok(())
+        Ok(PatchReport::default())
+    }
+}
+
+/// 按 `--ide` 给出的名称选择初始化器，返回其在 `ides` 中的下标
+pub fn find(ides: &[Box<dyn IdeInitializer>], names: &[String]) -> anyhow::Result<Vec<usize>> {
+    let mut chosen = Vec::new();
+    for name in names {
+        if name.eq_ignore_ascii_case(IdeNone.id()) {
+            continue;
+        }
+        let idx = ides
+            .iter()
+            .position(|i| i.id().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown IDE {}, available: {}, none",
+                    name,
+                    ides.iter().map(|i| i.id()).collect::<Vec<_>>().join(", ")
+                )
+            })?;
+        if !chosen.contains(&idx) {
+            chosen.push(idx);
+        }
     }
+    Ok(chosen)
 }
@@ -0,0 +1,130 @@
+use crate::creators::board::load_board_profiles;
+use crate::generate_gitignore::gitignore_sections;
+use crate::stm32cubemx::{Toolchain, get_toolchain};
+use crate::{creators, initializers};
+use clap::{Subcommand, ValueEnum};
+use serde::Serialize;
+
+#[derive(Subcommand)]
+pub enum ListCommands {
+    /// 芯片定义（`create --mcu`）
+    Mcus,
+    /// IDE 初始化器（`init --ide`）
+    Ides,
+    /// CubeMX 工具链（`--toolchain`）
+    Toolchains,
+    /// 板级配置（`create --board`）
+    Boards,
+    /// 内置的 gitignore 配置
+    GitignoreSections,
+}
+
+/// `list ides` 显示的初始化器信息
+#[derive(Serialize)]
+struct IdeInfo {
+    id: &'static str,
+    name: &'static str,
+    /// 要求工程使用的工具链
+    toolchain: Option<String>,
+}
+
+/// `list toolchains` 显示的工具链信息
+#[derive(Serialize)]
+struct ToolchainInfo {
+    /// 命令行中使用的名称
+    name: String,
+    /// CubeMX 中的名称
+    cubemx: &'static str,
+}
+
+fn toolchain_name(toolchain: Toolchain) -> String {
+    toolchain
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn print_json<T: Serialize>(value: &T) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+pub fn run_list(command: ListCommands, json: bool) -> anyhow::Result<()> {
+    match command {
+        ListCommands::Mcus => {
+            let mcus: Vec<_> = creators::all().iter().map(|m| m.info()).collect();
+            if json {
+                return print_json(&mcus);
+            }
+            for mcu in mcus {
+                println!("{:<16} {}", mcu.name, mcu.description);
+                println!(
+                    "{:<16} core: {}, fpu: {}, debug: {}",
+                    "",
+                    mcu.core,
+                    mcu.fpu.as_deref().unwrap_or("none"),
+                    mcu.debug_interfaces.join(", ")
+                );
+            }
+        }
+        ListCommands::Ides => {
+            let ides: Vec<IdeInfo> = initializers::all()
+                .iter()
+                .map(|i| IdeInfo {
+                    id: i.id(),
+                    name: i.name(),
+                    toolchain: i.toolchain().map(toolchain_name),
+                })
+                .collect();
+            if json {
+                return print_json(&ides);
+            }
+            for ide in ides {
+                println!("{:<8} {}", ide.id, ide.name);
+            }
+        }
+        ListCommands::Toolchains => {
+            let toolchains: Vec<ToolchainInfo> = Toolchain::value_variants()
+                .iter()
+                .map(|t| ToolchainInfo {
+                    name: toolchain_name(*t),
+                    cubemx: get_toolchain(t),
+                })
+                .collect();
+            if json {
+                return print_json(&toolchains);
+            }
+            for toolchain in toolchains {
+                println!("{:<16} {}", toolchain.name, toolchain.cubemx);
+            }
+        }
+        ListCommands::Boards => {
+            let boards = load_board_profiles();
+            if json {
+                return print_json(&boards);
+            }
+            for board in boards {
+                println!("{:<16} {} ({})", board.name, board.description, board.chip);
+            }
+        }
+        ListCommands::GitignoreSections => {
+            let configs = gitignore_sections();
+            if json {
+                return print_json(&configs);
+            }
+            for config in configs {
+                let state = |enabled: bool| if enabled { "" } else { " (disabled)" };
+                println!(
+                    "{}{}: {}",
+                    config.name,
+                    state(config.enabled),
+                    config.description
+                );
+                for section in config.sections {
+                    println!("  {}{}", section.name, state(section.enabled));
+                }
+            }
+        }
+    }
+    Ok(())
+}
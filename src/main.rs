@@ -1,28 +1,37 @@
+mod config;
 mod creators;
+mod doctor;
+mod dry_run;
 mod generate_gitignore;
+mod git;
 mod initializers;
+mod list;
 mod patches;
+mod pipeline;
+mod project;
+mod prompt;
 mod render;
 mod stm32cubemx;
 mod utils;
+mod verify;
 
 use crate::creators::CreateContext;
+use crate::creators::board::find_board;
 use crate::generate_gitignore::generate_gitignore;
-use crate::initializers::IdeInitArgs;
-use crate::patches::{apply_patch, Patch};
-use crate::render::render_file;
-use crate::stm32cubemx::{generate_code, get_toolchain, Toolchain};
-use crate::utils::get_author;
-use anyhow::anyhow;
-use chrono::Local;
+use crate::git::register_ioc_merge_driver;
+use crate::initializers::{FPUType, IdeInitArgs};
+use crate::list::{ListCommands, run_list};
+use crate::patches::{PatchReport, get_file, is_fatal};
+use crate::project::{InitConfig, ProjectConfig};
+use crate::render::{InitContext, render_file};
+use crate::stm32cubemx::ioc::IocFile;
+use crate::stm32cubemx::{Toolchain, find_ioc_file, generate_code, get_toolchain, ioc};
+use anyhow::{Context, anyhow};
 use clap::{Parser, Subcommand};
-use dialoguer::theme::ColorfulTheme;
-use dialoguer::{Confirm, MultiSelect, Select};
-use serde::Serialize;
-use std::path::Path;
-use std::process::{Command, Stdio};
-use std::{env, fs};
-use tracing::{error, info};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use tracing::{error, info, warn};
 
 #[derive(Subcommand)]
 enum Commands {
@@ -41,6 +50,80 @@ enum Commands {
     Generate {
         #[clap(short, long)]
         toolchain: Option<Toolchain>,
+        /// 生成后规范化 .ioc 文件，减少无意义的 diff
+        #[arg(long)]
+        normalize: bool,
+    },
+
+    /// .ioc 文件工具
+    #[command(subcommand)]
+    Ioc(IocCommands),
+
+    /// 检查 init 对 CMakeLists.txt、Makefile 等的修改和 UserCode 目录是否仍然存在
+    Verify {
+        /// 要检查的 IDE，可多次指定或用逗号分隔，省略时按 .ioc 中的工具链确定
+        #[arg(long = "ide", value_delimiter = ',')]
+        ides: Vec<String>,
+        /// 补回 FPU 配置时使用的类型，默认使用 init 时的选择
+        #[arg(long, short)]
+        fpu: Option<FPUType>,
+        /// 补回缺失的部分
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// 检查开发环境：git、STM32CubeMX、交叉编译工具链、构建工具和固件包
+    ///
+    /// 退出码：0 正常，2 有警告，3 有错误
+    Doctor,
+
+    /// 列出可用的芯片、IDE、工具链、板级配置和 gitignore 配置
+    List {
+        #[command(subcommand)]
+        command: ListCommands,
+        /// 以 JSON 输出，便于脚本和编辑器插件读取
+        #[arg(long, global = true)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum IocCommands {
+    /// 显示 .ioc 文件中的芯片、工程、时钟和引脚信息
+    Info {
+        /// .ioc 文件路径，默认使用当前目录下唯一的 .ioc 文件
+        file: Option<String>,
+    },
+
+    /// 按引脚、外设、时钟、RTOS 和工程设置分组比较两个 .ioc 文件
+    ///
+    /// 忽略时间戳注释和键顺序。也可作为 git external diff 使用：
+    /// git config diff.ioc.command "stm32tool ioc diff"
+    Diff {
+        /// <a> <b>，或 git external diff 传入的 7 个参数
+        #[arg(required = true, num_args = 2..=7)]
+        files: Vec<String>,
+    },
+
+    /// 按键三方合并 .ioc 文件，用作 git merge driver
+    ///
+    /// 结果写回 <OURS>，存在冲突时以非零状态退出
+    Merge {
+        /// 共同祖先版本（%O）
+        base: String,
+        /// 当前分支版本（%A），合并结果写回此文件
+        ours: String,
+        /// 另一分支版本（%B）
+        theirs: String,
+    },
+
+    /// 规范化 .ioc 文件：删除时间戳，按键名排序，工程内的绝对路径改为相对路径
+    Normalize {
+        /// .ioc 文件路径，默认使用当前目录下唯一的 .ioc 文件
+        file: Option<String>,
+        /// 只检查是否已规范化，不写回；未规范化时以非零状态退出
+        #[arg(long)]
+        check: bool,
     },
 }
 
@@ -49,10 +132,17 @@ struct CreateArgs {
     /// 项目名
     project_name: String,
 
-    /// 使用的工具链
+    /// 使用的工具链，默认使用 profile 中的设置或 cmake
     #[clap(short, long)]
-    #[arg(default_value = "cmake")]
-    toolchain: Toolchain,
+    toolchain: Option<Toolchain>,
+
+    /// 使用的板级配置（晶振、时基、调试接口、LED 和按键），会同时确定芯片
+    #[arg(long)]
+    board: Option<String>,
+
+    /// 使用的芯片定义，如 STM32F407VETx，省略时交互选择
+    #[arg(long)]
+    mcu: Option<String>,
 
     /// 是否在创建后立即初始化项目
     #[arg(long)]
@@ -66,23 +156,44 @@ struct CreateArgs {
 #[derive(Parser, Debug)]
 struct InitArgs {
     /// 跳过生成 UserCode 目录结构
-    #[arg(long, default_value_t = false)]
+    #[arg(
+        long,
+        default_value_t = false,
+        overrides_with = "no_skip_generate_user_code"
+    )]
     skip_generate_user_code: bool,
+    /// 生成 UserCode 目录结构，覆盖项目配置或 profile 中的 skip_generate_user_code
+    #[arg(long, overrides_with = "skip_generate_user_code")]
+    no_skip_generate_user_code: bool,
     /// 跳过生成 .clang-format
-    #[arg(long, default_value_t = false)]
+    #[arg(
+        long,
+        default_value_t = false,
+        overrides_with = "no_skip_generate_clang_format"
+    )]
     skip_generate_clang_format: bool,
+    /// 生成 .clang-format，覆盖项目配置或 profile 中的 skip_generate_clang_format
+    #[arg(long, overrides_with = "skip_generate_clang_format")]
+    no_skip_generate_clang_format: bool,
     /// 跳过非侵入式头文件配置
     ///
     /// 只有当 skip_generate_user_code 未启用时生效
     #[arg(
         long,
         requires_if("false", "skip_generate_user_code"),
-        default_value_t = false
+        default_value_t = false,
+        overrides_with = "no_skip_non_intrusive_headers"
     )]
     skip_non_intrusive_headers: bool,
+    /// 配置非侵入式头文件，覆盖项目配置或 profile 中的 skip_non_intrusive_headers
+    #[arg(long, overrides_with = "skip_non_intrusive_headers")]
+    no_skip_non_intrusive_headers: bool,
     /// 强制重新生成
     #[arg(long)]
     force: bool,
+    /// 有补丁未能应用（找不到锚点、目标文件不存在、匹配多处）或 IDE 初始化失败时以错误退出
+    #[arg(long)]
+    strict: bool,
     #[command(flatten)]
     init_args: IdeInitArgs,
 }
@@ -91,20 +202,69 @@ struct InitArgs {
 #[command(name = "stm32-project-tool")]
 #[command(about = "STM32 project helper tool", long_about = None)]
 struct Cli {
+    /// 实时输出 STM32CubeMX 的输出
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// 确认提示一律回答是（如覆盖已存在的项目）
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// 从不交互，需要输入时直接报错；stdin 不是终端时自动启用
+    #[arg(long, global = true)]
+    no_input: bool,
+
+    /// 只打印将要执行的操作（渲染的文件、补丁 diff、CubeMX 脚本、git 命令），不修改磁盘也不启动外部程序
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// STM32CubeMX 可执行文件路径；`fixture:<dir>` 回放录制的生成结果，`record:<dir>` 运行并录制
+    #[arg(long, global = true, env = "STM32TOOL_CUBEMX")]
+    cubemx: Option<String>,
+
+    /// STM32CubeMX 运行超时（秒），0 表示不限制
+    #[arg(long, global = true, default_value_t = 600)]
+    timeout: u64,
+
+    /// 使用用户配置中的 profile 作为默认选项，默认为用户配置中的 `profile`
+    #[arg(long, global = true, env = "STM32TOOL_PROFILE")]
+    profile: Option<String>,
+
+    /// init 流水线文件（目录、模板和补丁），默认使用项目配置或 profile 中的 `pipeline`，否则使用内置流水线
+    #[arg(long, global = true, env = "STM32TOOL_PIPELINE")]
+    pipeline: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
-#[derive(Serialize)]
-struct InitContext {
-    author: String,
-    date: String,
-    year: String,
-}
-
 fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
+    // 日志输出到 stderr，stdout 只留给命令结果（如 `list --json`）
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
     let cli = Cli::parse();
+    dry_run::set_enabled(cli.dry_run);
+    prompt::set_mode(cli.yes, cli.no_input);
+    stm32cubemx::output::set_verbose(cli.verbose);
+    stm32cubemx::process::set_timeout(cli.timeout);
+    stm32cubemx::backend::set_backend(cli.cubemx)?;
+    stm32cubemx::process::install_ctrlc_handler()?;
+    // 只有这些命令使用 profile 和流水线，失效的配置不影响 `ioc merge` 等命令
+    if matches!(
+        cli.command,
+        Commands::Init(_)
+            | Commands::Create(_)
+            | Commands::Generate { .. }
+            | Commands::Verify { .. }
+    ) {
+        config::set_profile(cli.profile)?;
+        pipeline::set_pipeline(
+            cli.pipeline
+                .or(ProjectConfig::load().init.pipeline)
+                .or_else(|| config::profile().init.pipeline.clone()),
+        )?;
+    }
 
     match cli.command {
         Commands::Init(args) => {
@@ -114,51 +274,109 @@ fn main() -> anyhow::Result<()> {
             run_create(args)?;
         }
         Commands::Purge => {
-            let status = Command::new("git")
-                .args(vec!["clean", "-fdX"])
-                .stdout(Stdio::null()) // 屏蔽 stdout
-                .stderr(Stdio::null()) // 屏蔽 stderr
-                .status()?;
+            let status = dry_run::status(
+                Command::new("git")
+                    .args(vec!["clean", "-fdX"])
+                    .stdout(Stdio::null()) // 屏蔽 stdout
+                    .stderr(Stdio::null()), // 屏蔽 stderr
+            )?;
             if status.success() {
                 info!("purge successfully!");
             } else {
                 error!("purge failed!, {}", status);
             }
         }
-        Commands::Generate { toolchain } => {
-            generate_code(toolchain)?;
+        Commands::Generate {
+            toolchain,
+            normalize,
+        } => {
+            let mut project = ProjectConfig::load();
+            generate_code(toolchain.or(project.toolchain))?;
+            verify::reapply()?;
+            if toolchain.is_some() && toolchain != project.toolchain {
+                project.toolchain = toolchain;
+                project.save()?;
+            }
+            if normalize {
+                ioc::normalize::normalize_file(&find_ioc_file()?)?;
+            }
+        }
+        Commands::Ioc(command) => match command {
+            IocCommands::Info { file } => ioc::print_info(file)?,
+            IocCommands::Diff { files } => ioc::diff::run_diff(&files)?,
+            IocCommands::Merge { base, ours, theirs } => {
+                ioc::merge::run_merge(&base, &ours, &theirs)?
+            }
+            IocCommands::Normalize { file, check } => ioc::normalize::run_normalize(file, check)?,
+        },
+        Commands::Verify { ides, fpu, fix } => verify::run_verify(&ides, fpu, fix)?,
+        Commands::List { command, json } => run_list(command, json)?,
+        Commands::Doctor => {
+            let code = doctor::run_doctor();
+            if code != 0 {
+                process::exit(code);
+            }
         }
     }
 
     Ok(())
 }
 
-fn run_init(args: InitArgs) -> anyhow::Result<()> {
-    let ides = initializers::all();
-    let items: Vec<&str> = ides.iter().map(|i| i.name()).collect();
-
-    let chosen = MultiSelect::with_theme(&ColorfulTheme::default())
-        .with_prompt("Select IDEs to initialize")
-        .items(&items)
-        .interact()?;
+/// 记录到项目配置的流水线路径，项目内的文件使用相对路径
+fn recorded_pipeline() -> anyhow::Result<Option<PathBuf>> {
+    let Some(path) = pipeline::current().path() else {
+        return Ok(None);
+    };
+    let cwd = dry_run::current_dir()?;
+    Ok(Some(path.strip_prefix(&cwd).unwrap_or(path).to_path_buf()))
+}
 
-    // 渲染上下文
-    let author = get_author();
+fn run_init(mut args: InitArgs) -> anyhow::Result<()> {
+    // 命令行参数优先，其次是项目配置中记录的选择，最后是 profile
+    let mut project = ProjectConfig::load();
+    let profile = config::profile();
+    let recorded = &project.init.or(&profile.init);
+    // `--skip-*` 和 `--no-skip-*` 都未给出时才使用记录的值
+    let skip =
+        |on: bool, off: bool, recorded: Option<bool>| on || (!off && recorded.unwrap_or(false));
+    args.skip_generate_user_code = skip(
+        args.skip_generate_user_code,
+        args.no_skip_generate_user_code,
+        recorded.skip_generate_user_code,
+    );
+    args.skip_generate_clang_format = skip(
+        args.skip_generate_clang_format,
+        args.no_skip_generate_clang_format,
+        recorded.skip_generate_clang_format,
+    );
+    args.skip_non_intrusive_headers = skip(
+        args.skip_non_intrusive_headers,
+        args.no_skip_non_intrusive_headers,
+        recorded.skip_non_intrusive_headers,
+    );
+    args.init_args.fpu = args.init_args.fpu.or(recorded.fpu);
 
-    let now = Local::now();
-    let ctx = InitContext {
-        author,
-        date: now.format("%Y-%m-%d").to_string(),
-        year: now.format("%Y").to_string(),
+    let ides = initializers::all();
+    let chosen = if !args.init_args.ides.is_empty() {
+        initializers::find(&ides, &args.init_args.ides)?
+    } else if let Some(recorded) = &recorded.ides {
+        initializers::find(&ides, recorded)?
+    } else {
+        let items: Vec<&str> = ides.iter().map(|i| i.name()).collect();
+        prompt::multi_select("Select IDEs to initialize", &items, "--ide")?
     };
 
+    // 渲染上下文
+    let ctx = InitContext::new();
+
     // 初始化项目配置
     info!("Initializing git repository...");
-    let status = Command::new("git")
-        .arg("init")
-        .stdout(Stdio::null()) // 屏蔽 stdout
-        .stderr(Stdio::null()) // 屏蔽 stderr
-        .status();
+    let status = dry_run::status(
+        Command::new("git")
+            .arg("init")
+            .stdout(Stdio::null()) // 屏蔽 stdout
+            .stderr(Stdio::null()), // 屏蔽 stderr
+    );
     match status {
         Ok(status) if status.success() => {
             info!("Git repository initialized successfully!");
@@ -170,10 +388,24 @@ fn run_init(args: InitArgs) -> anyhow::Result<()> {
             error!("Failed to execute git: {}", e);
         }
     }
+    if let Err(e) = register_ioc_merge_driver() {
+        error!("Failed to register .ioc merge driver: {}", e);
+    }
     info!("Generating .gitignore file...");
     generate_gitignore(None, args.force)?;
 
-    if !args.skip_generate_clang_format {
+    if !args.skip_generate_clang_format
+        && let Some(path) = &profile.clang_format
+    {
+        info!("Copying .clang-format from {}", path.display());
+        if dry_run::exists(".clang-format") && !args.force {
+            warn!("Skip existing .clang-format");
+        } else {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            dry_run::write(".clang-format", &content)?;
+        }
+    } else if !args.skip_generate_clang_format {
         info!("Generating .clang-format file");
         render_file(
             ".clang-format",
@@ -185,42 +417,23 @@ fn run_init(args: InitArgs) -> anyhow::Result<()> {
 
     if !args.skip_generate_user_code {
         info!("Generating user code directories...");
-        let directories: Vec<&str> = vec![
-            "UserCode/bsp",
-            "UserCode/drivers",
-            "UserCode/third_party",
-            "UserCode/libs",
-            "UserCode/interfaces",
-            "UserCode/controllers",
-            "UserCode/app",
-            "Modules",
-        ];
-        for dir in directories {
-            fs::create_dir_all(dir)?;
-            info!("Created dir {}", dir);
-        }
-        render_file(
-            "UserCode/app/app.h",
-            include_str!("templates/app.h.tmpl"),
-            &ctx,
-            args.force,
-        )?;
-        render_file(
-            "UserCode/app/app.c",
-            include_str!("templates/app.c.tmpl"),
-            &ctx,
-            args.force,
-        )?;
-        render_file(
-            "UserCode/README.md",
-            include_str!("templates/README.md.tmpl"),
-            &ctx,
-            args.force,
-        )?;
+        pipeline::current().generate_user_code(&ctx, args.force)?;
     }
 
-    for idx in chosen {
-        let _ = ides[idx].init(&args.init_args, args.force);
+    let mut report = PatchReport::default();
+    let mut failed = 0;
+    for &idx in &chosen {
+        match ides[idx].init(&args.init_args, args.force) {
+            Ok(r) => report.extend(r),
+            Err(e) if is_fatal(&e) => {
+                report.print_summary();
+                return Err(e.context(format!("Failed to initialize {}", ides[idx].name())));
+            }
+            Err(e) => {
+                error!("Failed to initialize {}: {}", ides[idx].name(), e);
+                failed += 1;
+            }
+        }
     }
 
     // 突然发现这个不需要
@@ -229,28 +442,49 @@ fn run_init(args: InitArgs) -> anyhow::Result<()> {
             info!("Skipping non-intrusive headers due to skip_generate_user_code");
         } else {
             info!("Generating non-intrusive headers");
-            apply_patch(
-                &Patch::Append {
-                    file: "CMakeLists_template.txt".to_string(),
-                    after: "add_executable".to_string(),
-                    insert: "\n# 非侵入式引入头文件\ntarget_compile_options(${PROJECT_NAME}.elf PRIVATE -include ${CMAKE_SOURCE_DIR}/UserCode/app/app.h)\n".to_string(),
-                    marker: "UserCode/app/app.h".to_string(),
-                })?;
-            apply_patch(&Patch::Append {
-                file: "Makefile".to_string(),
-                after: "CFLAGS += $(MCU)".to_string(),
-                insert: "\n# 非侵入式引入头文件\nCFLAGS += -include UserCode/app/app.h\n"
-                    .to_string(),
-                marker: "UserCode/app/app.h".to_string(),
-            })?;
+            // 只修改项目中存在的构建文件
+            for patch in &pipeline::current().headers {
+                if dry_run::exists(get_file(patch)) {
+                    report.apply(patch)?;
+                }
+            }
         }
     }
 
-    let status = Command::new("git").args(&["add", "."]).status()?;
+    report.print_summary();
+    if args.strict && (report.problems() > 0 || failed > 0) {
+        return Err(anyhow!(
+            "{} patch problem(s), {} IDE initializer(s) failed (--strict)",
+            report.problems(),
+            failed
+        ));
+    }
+
+    // 记录选择，generate 和之后的 init 据此重现
+    project.init = InitConfig {
+        ides: Some(
+            chosen
+                .iter()
+                .map(|&idx| ides[idx].id().to_string())
+                .collect(),
+        ),
+        fpu: Some(args.init_args.fpu()),
+        skip_generate_user_code: Some(args.skip_generate_user_code),
+        skip_generate_clang_format: Some(args.skip_generate_clang_format),
+        skip_non_intrusive_headers: Some(args.skip_non_intrusive_headers),
+        pipeline: recorded_pipeline()?,
+    };
+    if project.toolchain.is_none() {
+        project.toolchain = find_ioc_file()
+            .and_then(IocFile::load)
+            .ok()
+            .and_then(|ioc| ioc.toolchain());
+    }
+    project.save()?;
+
+    let status = dry_run::status(Command::new("git").args(["add", "."]))?;
     if status.success() {
-        let status = Command::new("git")
-            .args(&["commit", "-m", "Initial commit"])
-            .status()?;
+        let status = dry_run::status(Command::new("git").args(["commit", "-m", "Initial commit"]))?;
         if !status.success() {
             error!("Git first commit failed");
         }
@@ -261,34 +495,95 @@ fn run_init(args: InitArgs) -> anyhow::Result<()> {
     info!("STM32 project initialized!");
     Ok(())
 }
-fn run_create(args: CreateArgs) -> anyhow::Result<()> {
+fn run_create(mut args: CreateArgs) -> anyhow::Result<()> {
+    // 命令行参数优先，其次是 profile
+    let profile = config::profile();
+    if args.board.is_none() && args.mcu.is_none() {
+        args.board = profile.board.clone();
+    }
+    let toolchain = args
+        .toolchain
+        .or(profile.toolchain)
+        .unwrap_or(Toolchain::CMake);
+    let board = args.board.as_deref().map(find_board).transpose()?;
+    let mcus = creators::all();
+    let find_mcu = |name: &str| {
+        mcus.iter()
+            .position(|m| m.name().eq_ignore_ascii_case(name))
+    };
+    let board_mcu = match &board {
+        Some(board) => {
+            info!("Using board {} ({})", board.name, board.description);
+            let idx = find_mcu(&board.chip)
+                .ok_or_else(|| anyhow!("Board {} uses unknown MCU {}", board.name, board.chip))?;
+            if let Some(mcu) = &args.mcu
+                && !mcu.eq_ignore_ascii_case(&board.chip)
+            {
+                return Err(anyhow!(
+                    "Board {} uses {}, which conflicts with --mcu {}",
+                    board.name,
+                    board.chip,
+                    mcu
+                ));
+            }
+            Some(idx)
+        }
+        None => match &args.mcu {
+            Some(mcu) => Some(find_mcu(mcu).ok_or_else(|| {
+                anyhow!(
+                    "Unknown MCU {}, available: {}",
+                    mcu,
+                    mcus.iter().map(|m| m.name()).collect::<Vec<_>>().join(", ")
+                )
+            })?),
+            None => None,
+        },
+    };
+
+    // 选择芯片，指定了板级配置时使用其芯片
+    let chosen = match board_mcu {
+        Some(idx) => idx,
+        None => {
+            let items: Vec<String> = mcus
+                .iter()
+                .map(|i| match i.description() {
+                    "" => i.name().to_string(),
+                    description => format!("{} ({})", i.name(), description),
+                })
+                .collect();
+            prompt::select("Choose MCU", &items, "--mcu or --board")?
+        }
+    };
+
+    // 删除已有项目前解析板级配置，时钟等无法满足时不破坏已有内容
+    let board_context = mcus[chosen].board(board.as_ref())?;
+
+    // 创建前检查 --ide，避免生成完成后才报错
+    if args.run_init {
+        initializers::find(&initializers::all(), &args.init_args.init_args.ides)?;
+    }
+
     let path = Path::new(&args.project_name);
-    if path.exists() {
-        let result = Confirm::new()
-            .with_prompt(
-                "Project already exists. Regenerate? This will delete all existing content.",
-            )
-            .default(false) // false 对应 [y/N] 的 N
-            .interact()?;
-        if !result {
+    if dry_run::exists(path) {
+        if dry_run::enabled() {
+            // 不提示，但计划中说明会请求确认
+            info!(
+                "[dry-run] Would ask to regenerate existing project {}, deleting all its content",
+                path.display()
+            );
+        } else if !prompt::confirm(
+            "Project already exists. Regenerate? This will delete all existing content.",
+            false, // false 对应 [y/N] 的 N
+        )? {
             info!("Creation aborted!");
             return Err(anyhow!("Creation aborted!"));
         }
-        fs::remove_dir_all(path)?;
+        dry_run::remove_dir_all(path)?;
     }
-    fs::create_dir_all(&args.project_name)?;
-    env::set_current_dir(&args.project_name)?;
-
-    let current_dir = env::current_dir()?;
-
-    // 选择芯片
-    let mcus = creators::all();
-    let items: Vec<&str> = mcus.iter().map(|i| i.name()).collect();
-
-    let chosen = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("Choose MCU")
-        .items(&items)
-        .interact()?;
+    dry_run::create_dir_all(&args.project_name)?;
+    // dry-run 时只切换虚拟的当前目录，之后的文件和 git 命令都相对于新项目
+    dry_run::set_current_dir(&args.project_name)?;
+    let current_dir = dry_run::current_dir()?;
 
     let ctx = CreateContext {
         project_name: &args.project_name,
@@ -297,13 +592,22 @@ fn run_create(args: CreateArgs) -> anyhow::Result<()> {
             .join(format!("{}.ioc", args.project_name))
             .to_string_lossy()
             .to_string(),
-        toolchain: get_toolchain(&args.toolchain),
-        generate_under_root: args.toolchain == Toolchain::STM32CubeIDE,
+        toolchain: get_toolchain(&toolchain),
+        generate_under_root: toolchain == Toolchain::STM32CubeIDE,
+        board: board_context,
     };
 
-    info!("Using toolchain {}", get_toolchain(&args.toolchain));
+    info!("Using toolchain {}", get_toolchain(&toolchain));
     mcus[chosen].run(&ctx)?;
 
+    ProjectConfig {
+        mcu: Some(mcus[chosen].name().to_string()),
+        board: args.board.clone(),
+        toolchain: Some(toolchain),
+        ..Default::default()
+    }
+    .save()?;
+
     if args.run_init {
         info!("Running init process");
         run_init(args.init_args)?;
@@ -0,0 +1,729 @@
+use crate::patches::{DocOp, DocPatch};
+use roxmltree::{Document, Node};
+use serde_json::{Map, Value};
+use std::io;
+use tracing::warn;
+
+/// 结构化文档的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Xml,
+}
+
+/// 对文档的修改结果
+pub enum Edit {
+    /// 修改后的内容
+    Changed(String),
+    /// 已是目标状态
+    Unchanged,
+    /// 路径不存在或类型不符
+    NotFound,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// 对文档内容应用补丁
+pub fn apply(format: Format, content: &str, patch: &DocPatch) -> io::Result<Edit> {
+    match format {
+        Format::Json => {
+            let mut root: Value = serde_json::from_str(content)
+                .map_err(|e| invalid(format!("{}: {}", patch.file, e)))?;
+            Ok(match edit_value(&mut root, patch) {
+                Some(true) => Edit::Changed(to_json(&root, content)?),
+                Some(false) => Edit::Unchanged,
+                None => Edit::NotFound,
+            })
+        }
+        // YAML 经 `serde_json::Value` 重新输出：文件有修改时注释全部丢失，
+        // 引号、缩进和流式写法按 serde_yaml 的格式重写。未修改时不写回
+        Format::Yaml => {
+            let mut root: Value = serde_yaml::from_str(content)
+                .map_err(|e| invalid(format!("{}: {}", patch.file, e)))?;
+            Ok(match edit_value(&mut root, patch) {
+                Some(true) => {
+                    if content
+                        .lines()
+                        .any(|line| line.trim_start().starts_with('#'))
+                    {
+                        warn!("Comments in {} are dropped by the yaml patch", patch.file);
+                    }
+                    Edit::Changed(serde_yaml::to_string(&root).map_err(io::Error::other)?)
+                }
+                Some(false) => Edit::Unchanged,
+                None => Edit::NotFound,
+            })
+        }
+        Format::Xml => edit_xml(content, patch),
+    }
+}
+
+/// 按原文件的缩进输出 JSON
+fn to_json(value: &Value, original: &str) -> io::Result<String> {
+    let indent = original
+        .lines()
+        .find(|line| line.starts_with([' ', '\t']))
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .unwrap_or("  ");
+    let mut out = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    serde::Serialize::serialize(value, &mut serializer).map_err(io::Error::other)?;
+    let mut json = String::from_utf8(out).map_err(io::Error::other)?;
+    if original.ends_with('\n') {
+        json.push('\n');
+    }
+    Ok(json)
+}
+
+/// 找到路径对应的节点，`create` 时补全缺少的对象
+fn navigate<'a>(root: &'a mut Value, path: &[String], create: bool) -> Option<&'a mut Value> {
+    let mut node = root;
+    for segment in path {
+        if create && node.is_null() {
+            *node = Value::Object(Map::new());
+        }
+        node = match node {
+            Value::Object(map) => {
+                if create && !map.contains_key(segment) {
+                    map.insert(segment.clone(), Value::Null);
+                }
+                map.get_mut(segment)?
+            }
+            Value::Array(items) => items.get_mut(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(node)
+}
+
+/// 深度合并对象，返回是否有修改
+fn merge(target: &mut Value, value: &Value) -> bool {
+    match (target, value) {
+        (Value::Object(target), Value::Object(value)) => {
+            let mut changed = false;
+            for (key, value) in value {
+                match target.get_mut(key) {
+                    Some(existing) => changed |= merge(existing, value),
+                    None => {
+                        target.insert(key.clone(), value.clone());
+                        changed = true;
+                    }
+                }
+            }
+            changed
+        }
+        (target, value) if target != value => {
+            *target = value.clone();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// `append`/`remove` 的值，列表表示多项
+fn items(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        value => vec![value],
+    }
+}
+
+/// 修改 JSON/YAML 的值，返回是否有修改，路径不存在或类型不符时为 `None`
+fn edit_value(root: &mut Value, patch: &DocPatch) -> Option<bool> {
+    let value = &patch.value;
+    match patch.op {
+        DocOp::Set => {
+            let target = navigate(root, &patch.path, true)?;
+            if target == value {
+                return Some(false);
+            }
+            *target = value.clone();
+            Some(true)
+        }
+        DocOp::Merge => {
+            let target = navigate(root, &patch.path, true)?;
+            if !value.is_object() || !(target.is_object() || target.is_null()) {
+                return None;
+            }
+            Some(merge(target, value))
+        }
+        DocOp::Append => {
+            let target = navigate(root, &patch.path, true)?;
+            if target.is_null() {
+                *target = Value::Array(Vec::new());
+            }
+            let array = target.as_array_mut()?;
+            let mut changed = false;
+            for item in items(value) {
+                if !array.contains(item) {
+                    array.push(item.clone());
+                    changed = true;
+                }
+            }
+            Some(changed)
+        }
+        DocOp::Remove => {
+            let Some(target) = navigate(root, &patch.path, false) else {
+                return Some(false);
+            };
+            let array = target.as_array_mut()?;
+            let remove = items(value);
+            let len = array.len();
+            array.retain(|item| !remove.contains(&item));
+            Some(array.len() != len)
+        }
+    }
+}
+
+/// XML 路径中的一段，`tag` 或 `tag[attr=value]`
+fn matches(node: &Node, selector: &str) -> bool {
+    let (tag, filter) = match selector.split_once('[') {
+        Some((tag, filter)) => (tag, Some(filter.trim_end_matches(']'))),
+        None => (selector, None),
+    };
+    node.is_element()
+        && node.tag_name().name() == tag
+        && filter.is_none_or(|filter| {
+            let (name, value) = filter.split_once('=').unwrap_or((filter, ""));
+            let value = value.trim_matches(|c| c == '\'' || c == '"');
+            node.attribute(name) == Some(value)
+        })
+}
+
+/// 按路径找到元素，第一段为根元素
+fn find_element<'a, 'input>(
+    doc: &'a Document<'input>,
+    path: &[String],
+) -> Option<Node<'a, 'input>> {
+    let (first, rest) = path.split_first()?;
+    let mut node = doc.root_element();
+    if !matches(&node, first) {
+        return None;
+    }
+    for selector in rest {
+        node = node.children().find(|child| matches(child, selector))?;
+    }
+    Some(node)
+}
+
+fn escape(text: &str, quote: bool) -> String {
+    let text = text.replace('&', "&amp;").replace('<', "&lt;");
+    if quote {
+        text.replace('"', "&quot;")
+    } else {
+        text.replace('>', "&gt;")
+    }
+}
+
+/// 元素的标签名在原文中的写法（含前缀）
+fn qname<'a>(content: &'a str, node: &Node) -> &'a str {
+    let start = node.range().start + 1;
+    let len = content[start..]
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(0);
+    &content[start..start + len]
+}
+
+/// 开始标签的结束位置（`>` 之后），自闭合标签为 `None`
+fn start_tag_end(content: &str, node: &Node) -> Option<usize> {
+    let from = node
+        .attributes()
+        .map(|a| a.range().end)
+        .max()
+        .unwrap_or(node.range().start + 1 + qname(content, node).len());
+    let end = from + content[from..].find('>')?;
+    (content.as_bytes()[end - 1] != b'/').then_some(end + 1)
+}
+
+/// 结束标签 `</tag>` 的开始位置，结束标签中可以有空白，如 `</tag >`
+fn end_tag_start(content: &str, node: &Node) -> Option<usize> {
+    content[..node.range().end].rfind("</")
+}
+
+/// 所在行的缩进
+fn indent_of(content: &str, pos: usize) -> &str {
+    let line_start = content[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let prefix = &content[line_start..pos];
+    if prefix.trim().is_empty() { prefix } else { "" }
+}
+
+/// 与属性顺序和空白无关的元素表示，用于比较
+fn canonical(node: &Node) -> String {
+    let mut attributes: Vec<String> = node
+        .attributes()
+        .map(|a| format!("{}={:?}", a.name(), a.value()))
+        .collect();
+    attributes.sort();
+    let children: String = node
+        .children()
+        .filter_map(|child| {
+            if child.is_element() {
+                Some(canonical(&child))
+            } else if child.is_text() {
+                child.text().map(|text| text.trim().to_string())
+            } else {
+                None
+            }
+        })
+        .collect();
+    format!(
+        "<{} {}>{}</>",
+        node.tag_name().name(),
+        attributes.join(" "),
+        children
+    )
+}
+
+fn splice(content: &str, range: std::ops::Range<usize>, insert: &str) -> String {
+    format!(
+        "{}{}{}",
+        &content[..range.start],
+        insert,
+        &content[range.end..]
+    )
+}
+
+/// `set` 的值转为文本
+fn scalar(value: &Value) -> io::Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(_) | Value::Bool(_) => Ok(value.to_string()),
+        _ => Err(invalid(format!(
+            "XML value must be a string, got {}",
+            value
+        ))),
+    }
+}
+
+/// 设置属性，已是目标值时为 `None`
+fn set_attribute(content: &str, node: &Node, name: &str, value: &str) -> Option<String> {
+    if node.attribute(name) == Some(value) {
+        return None;
+    }
+    let escaped = escape(value, true);
+    Some(match node.attributes().find(|a| a.name() == name) {
+        Some(attribute) => splice(content, attribute.range_value(), &escaped),
+        None => {
+            let pos = node
+                .attributes()
+                .map(|a| a.range().end)
+                .max()
+                .unwrap_or(node.range().start + 1 + qname(content, node).len());
+            splice(content, pos..pos, &format!(" {}=\"{}\"", name, escaped))
+        }
+    })
+}
+
+/// 设置元素的文本，有子元素时为 `Err(())`。注释、处理指令保持不变，只替换第一个文本节点
+fn set_text(content: &str, node: &Node, value: &str) -> Result<Option<String>, ()> {
+    if node.children().any(|child| child.is_element()) {
+        return Err(());
+    }
+    let text = node.children().find(|child| child.is_text());
+    if text.and_then(|text| text.text()) == Some(value) {
+        return Ok(None);
+    }
+    let escaped = escape(value, false);
+    Ok(Some(match text {
+        Some(text) => splice(content, text.range(), &escaped),
+        None => match start_tag_end(content, node) {
+            Some(pos) => splice(content, pos..pos, &escaped),
+            None => {
+                let end = node.range().end;
+                let tag = qname(content, node);
+                splice(content, end - 2..end, &format!(">{}</{}>", escaped, tag))
+            }
+        },
+    }))
+}
+
+/// 把片段作为最后一个子元素插入，沿用已有子元素的缩进
+fn append_child(content: &str, node: &Node, fragment: &str) -> Option<String> {
+    let parent_indent = indent_of(content, node.range().start);
+    if let Some(last) = node.children().rfind(|c| c.is_element()) {
+        let indent = indent_of(content, last.range().start);
+        let fragment = fragment.replace('\n', &format!("\n{}", indent));
+        let pos = last.range().end;
+        return Some(splice(
+            content,
+            pos..pos,
+            &format!("\n{}{}", indent, fragment),
+        ));
+    }
+    // 子元素的缩进单位取自父元素相对其上一级的缩进
+    let unit = node
+        .parent_element()
+        .and_then(|p| parent_indent.strip_prefix(indent_of(content, p.range().start)))
+        .filter(|unit| !unit.is_empty())
+        .unwrap_or("  ");
+    let indent = format!("{}{}", parent_indent, unit);
+    let fragment = fragment.replace('\n', &format!("\n{}", indent));
+    let inner = format!("\n{}{}\n{}", indent, fragment, parent_indent);
+    match start_tag_end(content, node) {
+        Some(start) => {
+            let end = end_tag_start(content, node)?;
+            content[start..end]
+                .trim()
+                .is_empty()
+                .then(|| splice(content, start..end, &inner))
+        }
+        None => {
+            let end = node.range().end;
+            let tag = qname(content, node);
+            Some(splice(
+                content,
+                end - 2..end,
+                &format!(">{}</{}>", inner, tag),
+            ))
+        }
+    }
+}
+
+fn parse<'input>(content: &'input str, patch: &DocPatch) -> io::Result<Document<'input>> {
+    Document::parse(content).map_err(|e| invalid(format!("{}: {}", patch.file, e)))
+}
+
+fn edit_xml(content: &str, patch: &DocPatch) -> io::Result<Edit> {
+    let (path, attribute) = match patch.path.split_last() {
+        Some((last, path)) if last.starts_with('@') => (path, Some(&last[1..])),
+        _ => (&patch.path[..], None),
+    };
+
+    match patch.op {
+        DocOp::Set => {
+            let doc = parse(content, patch)?;
+            let Some(node) = find_element(&doc, path) else {
+                return Ok(Edit::NotFound);
+            };
+            let value = scalar(&patch.value)?;
+            let result = match attribute {
+                Some(name) => Ok(set_attribute(content, &node, name, &value)),
+                None => set_text(content, &node, &value),
+            };
+            Ok(match result {
+                Ok(Some(new_content)) => Edit::Changed(new_content),
+                Ok(None) => Edit::Unchanged,
+                Err(()) => Edit::NotFound,
+            })
+        }
+        DocOp::Merge => {
+            let Some(attributes) = patch.value.as_object() else {
+                return Err(invalid(format!(
+                    "{}: merge needs a map of attributes",
+                    patch.file
+                )));
+            };
+            let mut current = content.to_string();
+            for (name, value) in attributes {
+                let value = scalar(value)?;
+                let doc = parse(&current, patch)?;
+                let Some(node) = find_element(&doc, path) else {
+                    return Ok(Edit::NotFound);
+                };
+                if let Some(new_content) = set_attribute(&current, &node, name, &value) {
+                    current = new_content;
+                }
+            }
+            Ok(if current == content {
+                Edit::Unchanged
+            } else {
+                Edit::Changed(current)
+            })
+        }
+        DocOp::Append | DocOp::Remove => {
+            let fragment = scalar(&patch.value)?;
+            let fragment_doc = parse(&fragment, patch)?;
+            let wanted = canonical(&fragment_doc.root_element());
+            let doc = parse(content, patch)?;
+            let Some(node) = find_element(&doc, path) else {
+                return Ok(if patch.op == DocOp::Remove {
+                    Edit::Unchanged
+                } else {
+                    Edit::NotFound
+                });
+            };
+            let existing: Vec<Node> = node
+                .children()
+                .filter(|child| child.is_element() && canonical(child) == wanted)
+                .collect();
+
+            if patch.op == DocOp::Append {
+                if !existing.is_empty() {
+                    return Ok(Edit::Unchanged);
+                }
+                return Ok(match append_child(content, &node, fragment.trim()) {
+                    Some(new_content) => Edit::Changed(new_content),
+                    None => Edit::NotFound,
+                });
+            }
+
+            if existing.is_empty() {
+                return Ok(Edit::Unchanged);
+            }
+            // 从后往前删除，连同所在行的缩进和换行
+            let mut new_content = content.to_string();
+            for child in existing.iter().rev() {
+                let range = child.range();
+                let mut start = range.start - indent_of(content, range.start).len();
+                if content[..start].ends_with('\n') {
+                    start -= 1;
+                }
+                new_content = splice(&new_content, start..range.end, "");
+            }
+            Ok(Edit::Changed(new_content))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patch(path: &[&str], op: DocOp, value: Value) -> DocPatch {
+        DocPatch {
+            file: "test".to_string(),
+            path: path.iter().map(|s| s.to_string()).collect(),
+            op,
+            value,
+        }
+    }
+
+    fn changed(format: Format, content: &str, patch: &DocPatch) -> String {
+        match apply(format, content, patch).unwrap() {
+            Edit::Changed(new_content) => new_content,
+            Edit::Unchanged => panic!(
+                "{:?} {:?} did not change the document",
+                patch.op, patch.path
+            ),
+            Edit::NotFound => panic!("{:?} {:?} not found", patch.op, patch.path),
+        }
+    }
+
+    /// 应用后得到 `expected`，再次应用不修改
+    fn assert_idempotent(format: Format, content: &str, patch: &DocPatch, expected: &str) {
+        let new_content = changed(format, content, patch);
+        assert_eq!(new_content, expected);
+        assert!(matches!(
+            apply(format, &new_content, patch).unwrap(),
+            Edit::Unchanged
+        ));
+    }
+
+    const JSON: &str = "{\n    \"name\": \"demo\",\n    \"list\": [\n        \"a\"\n    ],\n    \"opts\": {\n        \"x\": 1\n    }\n}\n";
+
+    #[test]
+    fn json_set() {
+        assert_idempotent(
+            Format::Json,
+            JSON,
+            &patch(&["opts", "y", "z"], DocOp::Set, json!(true)),
+            "{\n    \"name\": \"demo\",\n    \"list\": [\n        \"a\"\n    ],\n    \"opts\": {\n        \"x\": 1,\n        \"y\": {\n            \"z\": true\n        }\n    }\n}\n",
+        );
+    }
+
+    #[test]
+    fn json_merge() {
+        assert_idempotent(
+            Format::Json,
+            JSON,
+            &patch(&["opts"], DocOp::Merge, json!({"x": 2, "w": "v"})),
+            "{\n    \"name\": \"demo\",\n    \"list\": [\n        \"a\"\n    ],\n    \"opts\": {\n        \"x\": 2,\n        \"w\": \"v\"\n    }\n}\n",
+        );
+        assert!(matches!(
+            apply(
+                Format::Json,
+                JSON,
+                &patch(&["name"], DocOp::Merge, json!({"x": 1}))
+            )
+            .unwrap(),
+            Edit::NotFound
+        ));
+    }
+
+    #[test]
+    fn json_append_and_remove() {
+        let appended = "{\n    \"name\": \"demo\",\n    \"list\": [\n        \"a\",\n        \"b\"\n    ],\n    \"opts\": {\n        \"x\": 1\n    }\n}\n";
+        assert_idempotent(
+            Format::Json,
+            JSON,
+            &patch(&["list"], DocOp::Append, json!(["a", "b"])),
+            appended,
+        );
+        assert_idempotent(
+            Format::Json,
+            appended,
+            &patch(&["list"], DocOp::Remove, json!("b")),
+            JSON,
+        );
+        assert!(matches!(
+            apply(
+                Format::Json,
+                JSON,
+                &patch(&["name"], DocOp::Append, json!("x"))
+            )
+            .unwrap(),
+            Edit::NotFound
+        ));
+    }
+
+    const YAML: &str = "name: demo\nlist:\n- a\nopts:\n  x: 1\n";
+
+    #[test]
+    fn yaml_set_and_merge() {
+        assert_idempotent(
+            Format::Yaml,
+            YAML,
+            &patch(&["name"], DocOp::Set, json!("other")),
+            "name: other\nlist:\n- a\nopts:\n  x: 1\n",
+        );
+        assert_idempotent(
+            Format::Yaml,
+            YAML,
+            &patch(&["opts"], DocOp::Merge, json!({"y": [1, 2]})),
+            "name: demo\nlist:\n- a\nopts:\n  x: 1\n  y:\n  - 1\n  - 2\n",
+        );
+    }
+
+    #[test]
+    fn yaml_append_and_remove() {
+        let appended = "name: demo\nlist:\n- a\n- b\nopts:\n  x: 1\n";
+        assert_idempotent(
+            Format::Yaml,
+            YAML,
+            &patch(&["list"], DocOp::Append, json!("b")),
+            appended,
+        );
+        assert_idempotent(
+            Format::Yaml,
+            appended,
+            &patch(&["list"], DocOp::Remove, json!(["b", "c"])),
+            YAML,
+        );
+    }
+
+    #[test]
+    fn yaml_unchanged_keeps_comments() {
+        let content = "# comment\nname: demo\n";
+        assert!(matches!(
+            apply(
+                Format::Yaml,
+                content,
+                &patch(&["name"], DocOp::Set, json!("demo"))
+            )
+            .unwrap(),
+            Edit::Unchanged
+        ));
+    }
+
+    const XML: &str = "<?xml version=\"1.0\"?>\n<project>\n  <!-- keep -->\n  <target name=\"Debug\" opt=\"0\">\n    <define>A</define>\n  </target>\n</project>\n";
+
+    #[test]
+    fn xml_set() {
+        assert_idempotent(
+            Format::Xml,
+            XML,
+            &patch(
+                &["project", "target[name=Debug]", "@opt"],
+                DocOp::Set,
+                json!("2"),
+            ),
+            &XML.replace("opt=\"0\"", "opt=\"2\""),
+        );
+        assert_idempotent(
+            Format::Xml,
+            XML,
+            &patch(&["project", "target", "define"], DocOp::Set, json!("B & C")),
+            &XML.replace(">A<", ">B &amp; C<"),
+        );
+        assert!(matches!(
+            apply(
+                Format::Xml,
+                XML,
+                &patch(&["project", "target[name=Release]"], DocOp::Set, json!("x"))
+            )
+            .unwrap(),
+            Edit::NotFound
+        ));
+    }
+
+    #[test]
+    fn xml_set_keeps_comments() {
+        let set = |path: &[&str]| patch(path, DocOp::Set, json!("w"));
+        assert_idempotent(
+            Format::Xml,
+            "<tag><!-- c -->v</tag>",
+            &set(&["tag"]),
+            "<tag><!-- c -->w</tag>",
+        );
+        assert_idempotent(
+            Format::Xml,
+            "<tag><?pi x?></tag>",
+            &set(&["tag"]),
+            "<tag>w<?pi x?></tag>",
+        );
+        assert_idempotent(
+            Format::Xml,
+            "<a><tag>v</tag ></a >",
+            &set(&["a", "tag"]),
+            "<a><tag>w</tag ></a >",
+        );
+    }
+
+    #[test]
+    fn xml_merge() {
+        assert_idempotent(
+            Format::Xml,
+            XML,
+            &patch(
+                &["project", "target"],
+                DocOp::Merge,
+                json!({"opt": "1", "fpu": "hard"}),
+            ),
+            &XML.replace("opt=\"0\"", "opt=\"1\" fpu=\"hard\""),
+        );
+    }
+
+    #[test]
+    fn xml_append_and_remove() {
+        let appended = XML.replace(
+            "<define>A</define>\n",
+            "<define>A</define>\n    <define>B</define>\n",
+        );
+        assert_idempotent(
+            Format::Xml,
+            XML,
+            &patch(
+                &["project", "target"],
+                DocOp::Append,
+                json!("<define>B</define>"),
+            ),
+            &appended,
+        );
+        assert_idempotent(
+            Format::Xml,
+            &appended,
+            &patch(
+                &["project", "target"],
+                DocOp::Remove,
+                json!("<define>B</define>"),
+            ),
+            XML,
+        );
+        // 结束标签中有空白
+        assert_idempotent(
+            Format::Xml,
+            "<project>\n  <target>\n  </target >\n</project>\n",
+            &patch(
+                &["project", "target"],
+                DocOp::Append,
+                json!("<define>B</define>"),
+            ),
+            "<project>\n  <target>\n    <define>B</define>\n  </target >\n</project>\n",
+        );
+    }
+}
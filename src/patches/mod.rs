@@ -0,0 +1,1065 @@
+use crate::dry_run;
+use crate::patches::document::{Edit, Format};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use tracing::warn;
+
+mod document;
+
+/// `Append`、`InsertBefore` 在锚点匹配多行时选择的位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Occurrence {
+    /// 所有匹配的行
+    #[default]
+    All,
+    First,
+    Last,
+    /// 第 n 个匹配的行，从 1 开始，如 `occurrence: {nth: 2}`
+    Nth(usize),
+}
+
+/// 结构化补丁对 `path` 处的值的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocOp {
+    /// 设为 `value`，XML 中设置元素文本或 `@属性`
+    Set,
+    /// 深度合并对象，XML 中合并属性
+    Merge,
+    /// 向数组追加 `value` 中尚不存在的项，XML 中追加子元素片段
+    Append,
+    /// 从数组删除 `value` 中的项，XML 中删除相同的子元素
+    Remove,
+}
+
+/// 修改 JSON、YAML、XML 文档中 `path` 处的内容
+///
+/// JSON/YAML 的路径为各级键名或数组下标；XML 的路径从根元素开始，
+/// 每段为 `tag` 或 `tag[attr=value]`，最后一段可为 `@attr`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DocPatch {
+    pub file: String,
+    pub path: Vec<String>,
+    pub op: DocOp,
+    #[serde(default)]
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "mode")]
+pub enum Patch {
+    /// 在包含 `after` 的行之后插入，`marker` 已存在时跳过
+    #[serde(rename = "append")]
+    Append {
+        file: String,
+        after: String,
+        insert: String,
+        marker: String,
+        #[serde(default)]
+        occurrence: Occurrence,
+    },
+    /// 在包含 `before` 的行之前插入，`marker` 已存在时跳过
+    #[serde(rename = "insert_before")]
+    InsertBefore {
+        file: String,
+        before: String,
+        insert: String,
+        marker: String,
+        #[serde(default)]
+        occurrence: Occurrence,
+    },
+    #[serde(rename = "replace")]
+    Replace {
+        file: String,
+        find: String,
+        insert: String,
+    },
+    #[serde(rename = "regex_replace")]
+    RegexReplace {
+        file: String,
+        pattern: String,
+        insert: String,
+    },
+    /// 取消 `marker` 之后连续注释行的注释
+    #[serde(rename = "uncomment_block")]
+    UncommentBlock { file: String, marker: String },
+    /// 注释 `marker` 之后到空行为止的各行，`UncommentBlock` 的反操作
+    #[serde(rename = "comment_block")]
+    CommentBlock { file: String, marker: String },
+    /// 删除从包含 `begin` 的行到其后包含 `end` 的行（含两端），并在原位置写入 `marker` 行。
+    /// `begin` 和 `marker` 都不存在时说明锚点有误，而不是已经删除
+    #[serde(rename = "delete_block")]
+    DeleteBlock {
+        file: String,
+        begin: String,
+        end: String,
+        marker: String,
+    },
+    /// 确保存在内容为 `line` 的行，不存在时插入到包含 `after` 的行之后，省略 `after` 时追加到末尾
+    #[serde(rename = "ensure_line")]
+    EnsureLine {
+        file: String,
+        line: String,
+        after: Option<String>,
+    },
+    /// 在 CubeMX 生成文件的 `/* USER CODE BEGIN section */` 与对应的 END 之间插入，
+    /// `marker` 已在该区域中时跳过，找不到区域时报错
+    #[serde(rename = "user_code_block")]
+    UserCodeBlock {
+        file: String,
+        section: String,
+        insert: String,
+        marker: String,
+    },
+    #[serde(rename = "json")]
+    Json(DocPatch),
+    /// 有修改时整个文件按 serde_yaml 的格式重新输出，注释全部丢失、格式会变化；
+    /// 已是目标状态时不修改文件。需要保留注释的文件请改用 `ensure_line`、`append` 等文本模式
+    #[serde(rename = "yaml")]
+    Yaml(DocPatch),
+    /// 只修改目标位置的文本，其余内容保持不变
+    #[serde(rename = "xml")]
+    Xml(DocPatch),
+}
+
+/// 应用一个补丁的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchOutcome {
+    /// 已修改文件
+    Applied,
+    /// 补丁内容已存在，未修改
+    AlreadyPresent,
+    /// 目标文件不存在
+    FileMissing,
+    /// 找不到锚点（`after`、`find`、`marker` 等），未修改
+    AnchorNotFound,
+    /// 锚点匹配多处，已全部修改（`UncommentBlock`、`CommentBlock`、`UserCodeBlock` 只修改第一处）。
+    /// `Replace`、`RegexReplace` 按匹配次数，`Append`、`InsertBefore` 按 `occurrence` 选中的行数，
+    /// 只有一处时为 `Applied`
+    MultipleMatches,
+}
+
+impl PatchOutcome {
+    /// 是否为预期结果（已应用或已存在）
+    pub fn is_ok(self) -> bool {
+        matches!(self, PatchOutcome::Applied | PatchOutcome::AlreadyPresent)
+    }
+}
+
+impl fmt::Display for PatchOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PatchOutcome::Applied => "applied",
+            PatchOutcome::AlreadyPresent => "already present",
+            PatchOutcome::FileMissing => "file missing",
+            PatchOutcome::AnchorNotFound => "anchor not found",
+            PatchOutcome::MultipleMatches => "multiple matches",
+        })
+    }
+}
+
+/// 找不到 USER CODE 区域。CubeMX 生成的文件与预期不符，继续 init 没有意义，因此直接报错
+#[derive(Debug)]
+pub struct MissingUserCodeRegion {
+    pub file: String,
+    pub section: String,
+}
+
+impl fmt::Display for MissingUserCodeRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "USER CODE region {0} not found in {1}, expected /* USER CODE BEGIN {0} */ .. /* USER CODE END {0} */",
+            self.section, self.file
+        )
+    }
+}
+
+impl std::error::Error for MissingUserCodeRegion {}
+
+/// 是否为不能只记录在补丁结果中、必须中止的错误
+pub fn is_fatal(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<MissingUserCodeRegion>().is_some()
+}
+
+/// 文件使用的换行符，以第一个换行为准
+fn line_ending(content: &str) -> &'static str {
+    match content.find('\n') {
+        Some(i) if content[..i].ends_with('\r') => "\r\n",
+        _ => "\n",
+    }
+}
+
+/// 按匹配次数得出修改后的结果
+fn matched(count: usize) -> PatchOutcome {
+    match count {
+        0 => PatchOutcome::AnchorNotFound,
+        1 => PatchOutcome::Applied,
+        _ => PatchOutcome::MultipleMatches,
+    }
+}
+
+/// 在匹配 `anchor` 的行之前或之后插入 `insert`，没有选中的行时为 `None`
+fn insert_at(
+    content: &str,
+    anchor: &str,
+    insert: &str,
+    occurrence: Occurrence,
+    before: bool,
+) -> Option<(String, PatchOutcome)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let matches: Vec<usize> = (0..lines.len())
+        .filter(|&i| lines[i].contains(anchor))
+        .collect();
+    let selected: Vec<usize> = match occurrence {
+        Occurrence::All => matches.clone(),
+        Occurrence::First => matches.first().copied().into_iter().collect(),
+        Occurrence::Last => matches.last().copied().into_iter().collect(),
+        Occurrence::Nth(n) => n
+            .checked_sub(1)
+            .and_then(|i| matches.get(i))
+            .copied()
+            .into_iter()
+            .collect(),
+    };
+    if selected.is_empty() {
+        return None;
+    }
+
+    let mut result = Vec::with_capacity(lines.len() + selected.len());
+    for (i, line) in lines.into_iter().enumerate() {
+        let hit = selected.contains(&i);
+        if hit && before {
+            result.push(insert);
+        }
+        result.push(line);
+        if hit && !before {
+            result.push(insert);
+        }
+    }
+    let outcome = if selected.len() > 1 {
+        PatchOutcome::MultipleMatches
+    } else {
+        PatchOutcome::Applied
+    };
+    Some((result.join("\n") + "\n", outcome))
+}
+
+/// `marker` 所在行之后到空行（不含）为止的行号范围
+fn block_after(lines: &[String], marker: &str) -> Option<std::ops::Range<usize>> {
+    let start = lines.iter().position(|line| line.contains(marker))? + 1;
+    let end = lines[start..]
+        .iter()
+        .position(|line| line.trim().is_empty())
+        .map_or(lines.len(), |len| start + len);
+    Some(start..end)
+}
+
+/// USER CODE 区域的 BEGIN、END 行号及同名区域的数量
+fn user_code_block(lines: &[&str], section: &str) -> Option<(usize, usize, usize)> {
+    let begin_marker = format!("/* USER CODE BEGIN {} */", section);
+    let end_marker = format!("/* USER CODE END {} */", section);
+    let count = lines.iter().filter(|l| l.contains(&begin_marker)).count();
+    let begin = lines.iter().position(|l| l.contains(&begin_marker))?;
+    let end = begin
+        + lines[begin..]
+            .iter()
+            .position(|l| l.contains(&end_marker))?;
+    Some((begin, end, count))
+}
+
+/// 把 `find` 替换为 `insert`，返回结果和替换次数。`insert` 包含 `find` 时
+/// 跳过已有的 `insert`，避免重复应用时再次展开
+fn replace_outside(content: &str, find: &str, insert: &str) -> (String, usize) {
+    let replace = |part: &str| (part.replace(find, insert), part.matches(find).count());
+    if !insert.contains(find) {
+        return replace(content);
+    }
+    let parts: Vec<(String, usize)> = content.split(insert).map(replace).collect();
+    let count = parts.iter().map(|(_, count)| count).sum();
+    let parts: Vec<String> = parts.into_iter().map(|(part, _)| part).collect();
+    (parts.join(insert), count)
+}
+
+/// `find` 已全部替换为 `insert`：`insert` 存在，且 `insert` 之外不再有 `find`
+fn is_replaced(content: &str, find: &str, insert: &str) -> bool {
+    content.contains(insert) && replace_outside(content, find, insert).1 == 0
+}
+
+/// 用 `re` 把所有匹配替换为 `insert`，返回结果和匹配次数
+fn regex_replace(content: &str, re: &Regex, insert: &str) -> (String, usize) {
+    let count = re.find_iter(content).count();
+    (re.replace_all(content, insert).into_owned(), count)
+}
+
+/// 正则替换已应用：替换后内容不变；替换后不再匹配的正则，只能在 `insert`
+/// 不引用捕获组时按 `insert` 是否存在判断
+fn is_regex_replaced(content: &str, re: &Regex, insert: &str) -> bool {
+    let (new_content, count) = regex_replace(content, re, insert);
+    if count == 0 {
+        !insert.contains('$') && content.contains(insert)
+    } else {
+        new_content == content
+    }
+}
+
+/// 结构化补丁及其格式
+fn document(patch: &Patch) -> Option<(Format, &DocPatch)> {
+    match patch {
+        Patch::Json(doc) => Some((Format::Json, doc)),
+        Patch::Yaml(doc) => Some((Format::Yaml, doc)),
+        Patch::Xml(doc) => Some((Format::Xml, doc)),
+        _ => None,
+    }
+}
+
+pub fn apply_patch(patch: &Patch) -> anyhow::Result<PatchOutcome> {
+    let Ok(content) = dry_run::read_to_string(get_file(patch)) else {
+        return Ok(PatchOutcome::FileMissing);
+    };
+
+    let (new_content, outcome) = match patch {
+        Patch::Append {
+            after: anchor,
+            insert,
+            marker,
+            occurrence,
+            ..
+        }
+        | Patch::InsertBefore {
+            before: anchor,
+            insert,
+            marker,
+            occurrence,
+            ..
+        } => {
+            if content.contains(marker) {
+                return Ok(PatchOutcome::AlreadyPresent);
+            }
+            let before = matches!(patch, Patch::InsertBefore { .. });
+            match insert_at(&content, anchor, insert, *occurrence, before) {
+                Some(result) => result,
+                None => return Ok(PatchOutcome::AnchorNotFound),
+            }
+        }
+        Patch::Replace { find, insert, .. } => {
+            if is_replaced(&content, find, insert) {
+                return Ok(PatchOutcome::AlreadyPresent);
+            }
+            let (new_content, count) = replace_outside(&content, find, insert);
+            if count == 0 {
+                return Ok(PatchOutcome::AnchorNotFound);
+            }
+            (new_content, matched(count))
+        }
+        Patch::RegexReplace {
+            pattern, insert, ..
+        } => {
+            let re = Regex::new(pattern)?;
+            if is_regex_replaced(&content, &re, insert) {
+                return Ok(PatchOutcome::AlreadyPresent);
+            }
+            // 正则替换本就作用于所有匹配
+            let (new_content, count) = regex_replace(&content, &re, insert);
+            if count == 0 {
+                return Ok(PatchOutcome::AnchorNotFound);
+            }
+            (new_content, matched(count))
+        }
+        Patch::UncommentBlock { marker, .. } => {
+            let count = content.lines().filter(|line| line.contains(marker)).count();
+            if count == 0 {
+                return Ok(PatchOutcome::AnchorNotFound);
+            }
+            if is_applied(patch) == Some(true) {
+                return Ok(PatchOutcome::AlreadyPresent);
+            }
+            let mut lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+
+            let mut in_block = false;
+            for line in lines.iter_mut() {
+                if line.contains(marker) {
+                    in_block = true;
+                    continue; // marker 行本身保留
+                }
+
+                if in_block {
+                    if line.starts_with('#') {
+                        // 去掉行首 "# " 或 "#"
+                        *line = line.trim_start_matches('#').trim().to_string();
+                    } else {
+                        // 遇到非注释行/空行，说明 block 结束
+                        break;
+                    }
+                }
+            }
+
+            (lines.join("\n") + "\n", matched(count))
+        }
+        Patch::CommentBlock { marker, .. } => {
+            let count = content.lines().filter(|line| line.contains(marker)).count();
+            let mut lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+            let Some(block) = block_after(&lines, marker) else {
+                return Ok(PatchOutcome::AnchorNotFound);
+            };
+            if lines[block.clone()]
+                .iter()
+                .all(|line| line.starts_with('#'))
+            {
+                return Ok(PatchOutcome::AlreadyPresent);
+            }
+            for line in &mut lines[block] {
+                if !line.starts_with('#') {
+                    line.insert(0, '#');
+                }
+            }
+            (lines.join("\n") + "\n", matched(count))
+        }
+        Patch::DeleteBlock {
+            begin, end, marker, ..
+        } => {
+            let mut lines: Vec<&str> = content.lines().collect();
+            let mut count = 0;
+            let mut from = 0;
+            while let Some(start) = lines[from..]
+                .iter()
+                .position(|line| line.contains(begin.as_str()))
+                .map(|i| from + i)
+            {
+                let Some(len) = lines[start + 1..]
+                    .iter()
+                    .position(|line| line.contains(end.as_str()))
+                else {
+                    // 有开头没有结尾，不删除任何内容
+                    return Ok(PatchOutcome::AnchorNotFound);
+                };
+                // 第一处替换为 marker，其余直接删除
+                let replacement = if count == 0 {
+                    vec![marker.as_str()]
+                } else {
+                    vec![]
+                };
+                from = start + replacement.len();
+                lines.splice(start..=start + 1 + len, replacement);
+                count += 1;
+            }
+            if count == 0 {
+                return Ok(if content.contains(marker.as_str()) {
+                    PatchOutcome::AlreadyPresent
+                } else {
+                    PatchOutcome::AnchorNotFound
+                });
+            }
+            (lines.join("\n") + "\n", matched(count))
+        }
+        Patch::EnsureLine { line, after, .. } => {
+            if content.lines().any(|l| l.trim() == line.trim()) {
+                return Ok(PatchOutcome::AlreadyPresent);
+            }
+            match after {
+                Some(after) => match insert_at(&content, after, line, Occurrence::First, false) {
+                    Some(result) => result,
+                    None => return Ok(PatchOutcome::AnchorNotFound),
+                },
+                None => {
+                    let mut new_content = content.clone();
+                    if !new_content.is_empty() && !new_content.ends_with('\n') {
+                        new_content.push('\n');
+                    }
+                    new_content.push_str(line);
+                    new_content.push('\n');
+                    (new_content, PatchOutcome::Applied)
+                }
+            }
+        }
+        Patch::UserCodeBlock {
+            section,
+            insert,
+            marker,
+            ..
+        } => {
+            let lines: Vec<&str> = content.lines().collect();
+            let Some((begin, end, count)) = user_code_block(&lines, section) else {
+                return Err(MissingUserCodeRegion {
+                    file: get_file(patch).to_string(),
+                    section: section.clone(),
+                }
+                .into());
+            };
+            if lines[begin..end]
+                .iter()
+                .any(|l| l.contains(marker.as_str()))
+            {
+                return Ok(PatchOutcome::AlreadyPresent);
+            }
+            // 按 END 行的缩进插入到 END 之前
+            let indent = &lines[end][..lines[end].len() - lines[end].trim_start().len()];
+            let inserted = insert.lines().map(|line| {
+                if line.trim().is_empty() {
+                    String::new()
+                } else {
+                    format!("{}{}", indent, line)
+                }
+            });
+            let new_lines: Vec<String> = lines[..end]
+                .iter()
+                .map(|l| l.to_string())
+                .chain(inserted)
+                .chain(lines[end..].iter().map(|l| l.to_string()))
+                .collect();
+            (new_lines.join("\n") + "\n", matched(count))
+        }
+        Patch::Json(doc) | Patch::Yaml(doc) | Patch::Xml(doc) => {
+            let format = document(patch).map(|(format, _)| format).unwrap();
+            match document::apply(format, &content, doc)? {
+                Edit::Changed(new_content) => (new_content, PatchOutcome::Applied),
+                Edit::Unchanged => return Ok(PatchOutcome::AlreadyPresent),
+                Edit::NotFound => return Ok(PatchOutcome::AnchorNotFound),
+            }
+        }
+    };
+
+    // 按行处理的模式以 `\n` 拼接，写回时使用原文件的换行符。CubeMX 在 Windows 上生成 CRLF 文件，
+    // 统一为 LF 会改动整个文件
+    let new_content = if document(patch).is_none() && line_ending(&content) == "\r\n" {
+        new_content.replace("\r\n", "\n").replace('\n', "\r\n")
+    } else {
+        new_content
+    };
+    dry_run::write(get_file(patch), &new_content)?;
+    Ok(outcome)
+}
+
+/// 一次 init 中各补丁的结果
+#[derive(Default)]
+pub struct PatchReport {
+    entries: Vec<(String, PatchOutcome)>,
+}
+
+impl PatchReport {
+    /// 应用补丁并记录结果
+    pub fn apply(&mut self, patch: &Patch) -> anyhow::Result<PatchOutcome> {
+        let outcome = apply_patch(patch)?;
+        let mut what = describe(patch);
+        if outcome == PatchOutcome::AnchorNotFound {
+            what = format!("{} (anchor: {})", what, anchor(patch).trim());
+        }
+        self.entries.push((what, outcome));
+        Ok(outcome)
+    }
+
+    pub fn extend(&mut self, other: PatchReport) {
+        self.entries.extend(other.entries);
+    }
+
+    /// 非预期结果的数量
+    pub fn problems(&self) -> usize {
+        self.entries.iter().filter(|(_, o)| !o.is_ok()).count()
+    }
+
+    /// 打印补丁结果表，有非预期结果时输出警告
+    pub fn print_summary(&self) {
+        if self.entries.is_empty() {
+            return;
+        }
+        println!("Patch summary:");
+        for (what, outcome) in &self.entries {
+            println!("  {:<17} {}", outcome.to_string(), what);
+        }
+        let count = |o: PatchOutcome| self.entries.iter().filter(|(_, e)| *e == o).count();
+        println!(
+            "{} applied, {} already present, {} problem(s)",
+            count(PatchOutcome::Applied),
+            count(PatchOutcome::AlreadyPresent),
+            self.problems()
+        );
+        if self.problems() > 0 {
+            warn!(
+                "{} patch(es) were not applied cleanly, the generated files may have changed",
+                self.problems()
+            );
+        }
+    }
+}
+
+/// 检查补丁本身是否有效，如正则表达式能否编译
+pub fn validate(patch: &Patch) -> anyhow::Result<()> {
+    match patch {
+        Patch::RegexReplace { file, pattern, .. } => {
+            Regex::new(pattern)
+                .map_err(|e| anyhow::anyhow!("Invalid pattern for {}: {}", file, e))?;
+        }
+        // marker 包含 begin 时删除后仍能找到 begin，无法判断是否已删除
+        Patch::DeleteBlock {
+            file,
+            begin,
+            marker,
+            ..
+        } if marker.is_empty() || marker.contains(begin.as_str()) => {
+            return Err(anyhow::anyhow!(
+                "delete_block marker for {} must be non-empty and must not contain begin",
+                file
+            ));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 补丁是否已应用，目标文件不存在时为 `None`
+pub fn is_applied(patch: &Patch) -> Option<bool> {
+    let content = dry_run::read_to_string(get_file(patch)).ok()?;
+    let applied = match patch {
+        Patch::Append { marker, .. } | Patch::InsertBefore { marker, .. } => {
+            content.contains(marker)
+        }
+        Patch::Replace { find, insert, .. } => is_replaced(&content, find, insert),
+        Patch::RegexReplace {
+            pattern, insert, ..
+        } => {
+            // 无效的正则在加载流水线时已报告，这里按未应用处理
+            Regex::new(pattern).is_ok_and(|re| is_regex_replaced(&content, &re, insert))
+        }
+        Patch::UncommentBlock { marker, .. } => {
+            // marker 的下一行不再是注释
+            let mut lines = content.lines().skip_while(|line| !line.contains(marker));
+            lines.next().is_some() && lines.next().is_some_and(|line| !line.starts_with('#'))
+        }
+        Patch::CommentBlock { marker, .. } => {
+            let lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+            block_after(&lines, marker)
+                .is_some_and(|block| lines[block].iter().all(|line| line.starts_with('#')))
+        }
+        Patch::DeleteBlock { begin, marker, .. } => {
+            !content.contains(begin.as_str()) && content.contains(marker.as_str())
+        }
+        Patch::EnsureLine { line, .. } => content.lines().any(|l| l.trim() == line.trim()),
+        Patch::UserCodeBlock {
+            section, marker, ..
+        } => {
+            let lines: Vec<&str> = content.lines().collect();
+            user_code_block(&lines, section).is_some_and(|(begin, end, _)| {
+                lines[begin..end]
+                    .iter()
+                    .any(|l| l.contains(marker.as_str()))
+            })
+        }
+        Patch::Json(doc) | Patch::Yaml(doc) | Patch::Xml(doc) => {
+            let format = document(patch).map(|(format, _)| format).unwrap();
+            matches!(document::apply(format, &content, doc), Ok(Edit::Unchanged))
+        }
+    };
+    Some(applied)
+}
+
+/// 用于报告的补丁描述，如 `CMakeLists.txt: include_directories(UserCode)`
+pub fn describe(patch: &Patch) -> String {
+    let what = match patch {
+        Patch::Append { marker, .. } | Patch::InsertBefore { marker, .. } => {
+            marker.trim().to_string()
+        }
+        Patch::Replace { insert, .. } => insert.trim().to_string(),
+        Patch::RegexReplace { insert, .. } => insert.trim().to_string(),
+        Patch::UncommentBlock { marker, .. } => marker.trim().to_string(),
+        Patch::CommentBlock { marker, .. } => format!("comment out {}", marker.trim()),
+        Patch::DeleteBlock { begin, end, .. } => {
+            format!("delete {} .. {}", begin.trim(), end.trim())
+        }
+        Patch::EnsureLine { line, .. } => line.trim().to_string(),
+        Patch::UserCodeBlock {
+            section, marker, ..
+        } => format!("{} in USER CODE {}", marker.trim(), section),
+        Patch::Json(doc) | Patch::Yaml(doc) | Patch::Xml(doc) => {
+            let op = format!("{:?}", doc.op).to_lowercase();
+            format!("{} {} {}", op, doc.path.join(" > "), doc.value)
+        }
+    };
+    format!("{}: {}", get_file(patch), what)
+}
+
+/// 替换补丁中的变量，如 `@mfpu@`
+pub fn substitute(patch: &Patch, vars: &[(&str, &str)]) -> Patch {
+    let mut patch = patch.clone();
+    let fields: Vec<&mut String> = match &mut patch {
+        Patch::Append {
+            after,
+            insert,
+            marker,
+            ..
+        } => vec![after, insert, marker],
+        Patch::InsertBefore {
+            before,
+            insert,
+            marker,
+            ..
+        } => vec![before, insert, marker],
+        Patch::Replace { find, insert, .. } => vec![find, insert],
+        Patch::RegexReplace {
+            pattern, insert, ..
+        } => vec![pattern, insert],
+        Patch::UncommentBlock { marker, .. } | Patch::CommentBlock { marker, .. } => {
+            vec![marker]
+        }
+        Patch::DeleteBlock {
+            begin, end, marker, ..
+        } => vec![begin, end, marker],
+        Patch::UserCodeBlock {
+            section,
+            insert,
+            marker,
+            ..
+        } => vec![section, insert, marker],
+        Patch::EnsureLine { line, after, .. } => {
+            std::iter::once(line).chain(after.as_mut()).collect()
+        }
+        Patch::Json(doc) | Patch::Yaml(doc) | Patch::Xml(doc) => {
+            substitute_value(&mut doc.value, vars);
+            doc.path.iter_mut().collect()
+        }
+    };
+    for field in fields {
+        for (name, value) in vars {
+            *field = field.replace(name, value);
+        }
+    }
+    patch
+}
+
+/// 替换 JSON 值中各字符串里的变量
+fn substitute_value(value: &mut Value, vars: &[(&str, &str)]) {
+    match value {
+        Value::String(s) => {
+            for (name, value) in vars {
+                *s = s.replace(name, value);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| substitute_value(v, vars)),
+        Value::Object(map) => map.values_mut().for_each(|v| substitute_value(v, vars)),
+        _ => {}
+    }
+}
+
+/// 补丁定位用的锚点
+fn anchor(patch: &Patch) -> String {
+    match patch {
+        Patch::Append { after, .. } => after.clone(),
+        Patch::InsertBefore { before, .. } => before.clone(),
+        Patch::Replace { find, .. } => find.clone(),
+        Patch::RegexReplace { pattern, .. } => pattern.clone(),
+        Patch::UncommentBlock { marker, .. } | Patch::CommentBlock { marker, .. } => marker.clone(),
+        Patch::DeleteBlock { begin, end, .. } => format!("{} .. {}", begin, end),
+        Patch::EnsureLine { after, .. } => after.clone().unwrap_or_default(),
+        Patch::UserCodeBlock { section, .. } => {
+            format!(
+                "/* USER CODE BEGIN {0} */ .. /* USER CODE END {0} */",
+                section
+            )
+        }
+        Patch::Json(doc) | Patch::Yaml(doc) | Patch::Xml(doc) => doc.path.join(" > "),
+    }
+}
+
+pub fn get_file(patch: &Patch) -> &str {
+    match patch {
+        Patch::Append { file, .. } => file,
+        Patch::InsertBefore { file, .. } => file,
+        Patch::Replace { file, .. } => file,
+        Patch::RegexReplace { file, .. } => file,
+        Patch::UncommentBlock { file, .. } => file,
+        Patch::CommentBlock { file, .. } => file,
+        Patch::DeleteBlock { file, .. } => file,
+        Patch::EnsureLine { file, .. } => file,
+        Patch::UserCodeBlock { file, .. } => file,
+        Patch::Json(doc) | Patch::Yaml(doc) | Patch::Xml(doc) => &doc.file,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// 测试用的临时文件，离开作用域时删除
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, content: &str) -> TempFile {
+            let path = std::env::temp_dir().join(format!(
+                "stm32tool-patch-{}-{}",
+                std::process::id(),
+                name
+            ));
+            fs::write(&path, content).unwrap();
+            TempFile(path)
+        }
+
+        fn path(&self) -> String {
+            self.0.to_string_lossy().to_string()
+        }
+
+        fn read(&self) -> String {
+            fs::read_to_string(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// 应用后得到 `expected`，再次应用不修改文件，`is_applied` 随之变化
+    fn assert_idempotent(file: &TempFile, patch: &Patch, expected: &str) {
+        assert_eq!(is_applied(patch), Some(false));
+        assert_eq!(apply_patch(patch).unwrap(), PatchOutcome::Applied);
+        assert_eq!(file.read(), expected);
+        assert_eq!(is_applied(patch), Some(true));
+        assert_eq!(apply_patch(patch).unwrap(), PatchOutcome::AlreadyPresent);
+        assert_eq!(file.read(), expected);
+    }
+
+    /// 找不到锚点时不修改文件
+    fn assert_anchor_not_found(file: &TempFile, patch: &Patch) {
+        let before = file.read();
+        assert_eq!(apply_patch(patch).unwrap(), PatchOutcome::AnchorNotFound);
+        assert_eq!(file.read(), before);
+        assert_eq!(is_applied(patch), Some(false));
+    }
+
+    #[test]
+    fn insert_before() {
+        let file = TempFile::new("insert-before", "a\nanchor\nb\n");
+        let patch = |before: &str| Patch::InsertBefore {
+            file: file.path(),
+            before: before.to_string(),
+            insert: "new".to_string(),
+            marker: "new".to_string(),
+            occurrence: Occurrence::All,
+        };
+        assert_anchor_not_found(&file, &patch("missing"));
+        assert_idempotent(&file, &patch("anchor"), "a\nnew\nanchor\nb\n");
+    }
+
+    #[test]
+    fn insert_before_occurrence() {
+        let file = TempFile::new("insert-before-nth", "x\nx\nx\n");
+        let patch = |occurrence| Patch::InsertBefore {
+            file: file.path(),
+            before: "x".to_string(),
+            insert: "new".to_string(),
+            marker: "new".to_string(),
+            occurrence,
+        };
+        assert_anchor_not_found(&file, &patch(Occurrence::Nth(4)));
+        assert_idempotent(&file, &patch(Occurrence::Nth(2)), "x\nnew\nx\nx\n");
+    }
+
+    #[test]
+    fn comment_block() {
+        let file = TempFile::new("comment-block", "#marker\nset(a)\nset(b)\n\nset(c)\n");
+        let patch = |marker: &str| Patch::CommentBlock {
+            file: file.path(),
+            marker: marker.to_string(),
+        };
+        assert_anchor_not_found(&file, &patch("#missing"));
+        assert_idempotent(
+            &file,
+            &patch("#marker"),
+            "#marker\n#set(a)\n#set(b)\n\nset(c)\n",
+        );
+        // uncomment_block 恢复原内容
+        let uncomment = Patch::UncommentBlock {
+            file: file.path(),
+            marker: "#marker".to_string(),
+        };
+        assert_idempotent(&file, &uncomment, "#marker\nset(a)\nset(b)\n\nset(c)\n");
+    }
+
+    #[test]
+    fn delete_block() {
+        let file = TempFile::new("delete-block", "keep\n# begin\nx\n# end\ntail\n");
+        let patch = |begin: &str| Patch::DeleteBlock {
+            file: file.path(),
+            begin: begin.to_string(),
+            end: "# end".to_string(),
+            marker: "# removed".to_string(),
+        };
+        assert_anchor_not_found(&file, &patch("# typo"));
+        assert_idempotent(&file, &patch("# begin"), "keep\n# removed\ntail\n");
+    }
+
+    #[test]
+    fn delete_block_without_begin_or_marker() {
+        // 既没有 begin 也没有 marker 时是锚点写错，不是已经删除
+        let file = TempFile::new("delete-block-typo", "keep\ntail\n");
+        let patch = Patch::DeleteBlock {
+            file: file.path(),
+            begin: "# begin".to_string(),
+            end: "# end".to_string(),
+            marker: "# removed".to_string(),
+        };
+        assert_anchor_not_found(&file, &patch);
+    }
+
+    #[test]
+    fn delete_block_without_end() {
+        let file = TempFile::new("delete-block-no-end", "# begin\nx\n");
+        let patch = Patch::DeleteBlock {
+            file: file.path(),
+            begin: "# begin".to_string(),
+            end: "# end".to_string(),
+            marker: "# removed".to_string(),
+        };
+        assert_anchor_not_found(&file, &patch);
+    }
+
+    #[test]
+    fn ensure_line() {
+        let file = TempFile::new("ensure-line", "a\nanchor\nb");
+        let patch = |after: Option<&str>| Patch::EnsureLine {
+            file: file.path(),
+            line: "new".to_string(),
+            after: after.map(str::to_string),
+        };
+        assert_anchor_not_found(&file, &patch(Some("missing")));
+        assert_idempotent(&file, &patch(Some("anchor")), "a\nanchor\nnew\nb\n");
+
+        let file = TempFile::new("ensure-line-end", "a\nb");
+        let patch = Patch::EnsureLine {
+            file: file.path(),
+            line: "new".to_string(),
+            after: None,
+        };
+        assert_idempotent(&file, &patch, "a\nb\nnew\n");
+    }
+
+    #[test]
+    fn replace() {
+        let file = TempFile::new("replace", "include(a)\n");
+        let patch = |find: &str, insert: &str| Patch::Replace {
+            file: file.path(),
+            find: find.to_string(),
+            insert: insert.to_string(),
+        };
+        assert_anchor_not_found(&file, &patch("missing", "x"));
+        // insert 包含 find
+        assert_idempotent(&file, &patch("include(a", "include(a b"), "include(a b)\n");
+        // insert 是 find 的一部分
+        assert_idempotent(&file, &patch("include(a b)", "include("), "include(\n");
+    }
+
+    #[test]
+    fn regex_replace() {
+        let file = TempFile::new("regex-replace", "OPT = -O0\nLIBS = a\n");
+        let patch = |pattern: &str, insert: &str| Patch::RegexReplace {
+            file: file.path(),
+            pattern: pattern.to_string(),
+            insert: insert.to_string(),
+        };
+        assert_anchor_not_found(&file, &patch("^CFLAGS = .*$", "CFLAGS = -g"));
+        // insert 引用捕获组，替换后仍然匹配
+        assert_idempotent(
+            &file,
+            &patch(r"(?m)^OPT = -O\d$", "OPT = -Og"),
+            "OPT = -Og\nLIBS = a\n",
+        );
+        assert_idempotent(
+            &file,
+            &patch(r"(?m)^(?<name>LIBS) = .*$", "${name} = a b"),
+            "OPT = -Og\nLIBS = a b\n",
+        );
+        // 替换后不再匹配，按 insert 是否存在判断
+        assert_idempotent(&file, &patch("-Og", "-Os"), "OPT = -Os\nLIBS = a b\n");
+    }
+
+    #[test]
+    fn regex_replace_multiple_matches() {
+        let file = TempFile::new("regex-replace-multiple", "A = 1\nB = 2\n");
+        let patch = Patch::RegexReplace {
+            file: file.path(),
+            pattern: r"(?m)^(\w+) = \d$".to_string(),
+            insert: "$1 = 0".to_string(),
+        };
+        assert_eq!(apply_patch(&patch).unwrap(), PatchOutcome::MultipleMatches);
+        assert_eq!(file.read(), "A = 0\nB = 0\n");
+        assert_eq!(is_applied(&patch), Some(true));
+        assert_eq!(apply_patch(&patch).unwrap(), PatchOutcome::AlreadyPresent);
+    }
+
+    #[test]
+    fn user_code_block() {
+        let file = TempFile::new(
+            "user-code-block",
+            "int main(void)\n{\n  /* USER CODE BEGIN 2 */\n  /* USER CODE END 2 */\n}\n",
+        );
+        let patch = |section: &str| Patch::UserCodeBlock {
+            file: file.path(),
+            section: section.to_string(),
+            insert: "app_init();\n\nif (x)\n  y();".to_string(),
+            marker: "app_init();".to_string(),
+        };
+        assert_idempotent(
+            &file,
+            &patch("2"),
+            "int main(void)\n{\n  /* USER CODE BEGIN 2 */\n  app_init();\n\n  if (x)\n    y();\n  /* USER CODE END 2 */\n}\n",
+        );
+
+        // 找不到区域时报错，指出文件和区域
+        let before = file.read();
+        let error = apply_patch(&patch("WHILE")).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("USER CODE region WHILE not found")
+        );
+        assert!(error.to_string().contains(&file.path()));
+        assert!(is_fatal(&error));
+        assert_eq!(file.read(), before);
+        assert_eq!(is_applied(&patch("WHILE")), Some(false));
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let file = TempFile::new(
+            "crlf",
+            "int main(void)\r\n{\r\n  /* USER CODE BEGIN 2 */\r\n  /* USER CODE END 2 */\r\n}\r\n",
+        );
+        let patch = Patch::UserCodeBlock {
+            file: file.path(),
+            section: "2".to_string(),
+            insert: "app_init();\nif (x)\n  y();".to_string(),
+            marker: "app_init();".to_string(),
+        };
+        assert_idempotent(
+            &file,
+            &patch,
+            "int main(void)\r\n{\r\n  /* USER CODE BEGIN 2 */\r\n  app_init();\r\n  if (x)\r\n    y();\r\n  /* USER CODE END 2 */\r\n}\r\n",
+        );
+
+        // 多行的 insert 也使用 CRLF
+        let file = TempFile::new("crlf-append", "a\r\nanchor\r\nb\r\n");
+        let patch = Patch::Append {
+            file: file.path(),
+            after: "anchor".to_string(),
+            insert: "\n# added\nx".to_string(),
+            marker: "# added".to_string(),
+            occurrence: Occurrence::All,
+        };
+        assert_idempotent(&file, &patch, "a\r\nanchor\r\n\r\n# added\r\nx\r\nb\r\n");
+    }
+
+    #[test]
+    fn missing_file() {
+        let patch = Patch::EnsureLine {
+            file: "/nonexistent/stm32tool/file".to_string(),
+            line: "x".to_string(),
+            after: None,
+        };
+        assert_eq!(apply_patch(&patch).unwrap(), PatchOutcome::FileMissing);
+        assert_eq!(is_applied(&patch), None);
+    }
+}
@@ -0,0 +1,536 @@
+use crate::creators::{self, McuInfo};
+use crate::dry_run;
+use crate::initializers::{self, FPUType};
+use crate::patches::{Patch, PatchReport, substitute, validate};
+use crate::project::{PROJECT_FILE, ProjectConfig};
+use crate::render::{InitContext, render_file};
+use crate::stm32cubemx::find_ioc_file;
+use crate::stm32cubemx::ioc::IocFile;
+use anyhow::{Context, anyhow};
+use include_dir::{Dir, include_dir};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+/// 内置的 init 流水线
+const DEFAULT_PIPELINE: &str = include_str!("config.yaml");
+
+/// 内置模板，流水线中用 `template` 引用
+static TEMPLATES_DIR: Dir = include_dir!("src/templates");
+
+/// 渲染的一个文件
+#[derive(Debug, Deserialize)]
+pub struct TemplateFile {
+    /// 输出路径，相对于项目根目录
+    pub path: String,
+    /// 内置模板名，如 `app.h.tmpl`
+    pub template: Option<String>,
+    /// 模板文件，相对于流水线文件所在目录
+    pub source: Option<PathBuf>,
+    /// 加载时读取的模板内容
+    #[serde(skip)]
+    content: String,
+}
+
+/// 硬件、软件浮点各自的补丁
+#[derive(Debug, Default, Deserialize)]
+pub struct FpuPatches {
+    #[serde(default)]
+    pub hard: Vec<Patch>,
+    #[serde(default)]
+    pub soft: Vec<Patch>,
+}
+
+/// 一个 IDE 的补丁
+#[derive(Debug, Default, Deserialize)]
+pub struct IdePatches {
+    #[serde(default)]
+    pub patches: Vec<Patch>,
+    #[serde(default)]
+    pub fpu: FpuPatches,
+}
+
+/// init 流水线，内置的见 `src/config.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct Pipeline {
+    /// 创建的用户代码目录
+    #[serde(default)]
+    pub directories: Vec<String>,
+    /// 渲染的用户代码文件
+    #[serde(default)]
+    pub templates: Vec<TemplateFile>,
+    /// 非侵入式引入头文件的补丁
+    #[serde(default)]
+    pub headers: Vec<Patch>,
+    /// 各 IDE 的补丁，键为 IDE 的 id
+    #[serde(default)]
+    pub ides: BTreeMap<String, IdePatches>,
+    /// 流水线文件路径，内置流水线为 `None`
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl TemplateFile {
+    /// 模板名，用于错误信息
+    fn name(&self) -> String {
+        match (&self.template, &self.source) {
+            (Some(name), _) => name.clone(),
+            (_, Some(source)) => source.display().to_string(),
+            _ => self.path.clone(),
+        }
+    }
+}
+
+impl Pipeline {
+    /// 解析流水线，`dir` 为 `source` 模板的相对路径基准
+    fn parse(content: &str, dir: &Path) -> anyhow::Result<Pipeline> {
+        let mut pipeline: Pipeline = serde_yaml::from_str(content)?;
+        for file in &mut pipeline.templates {
+            file.content = match (&file.template, &file.source) {
+                (Some(name), None) => TEMPLATES_DIR
+                    .get_file(name)
+                    .and_then(|f| f.contents_utf8())
+                    .ok_or_else(|| anyhow!("Unknown built-in template {} for {}", name, file.path))?
+                    .to_string(),
+                (None, Some(source)) => {
+                    let source = dir.join(source);
+                    fs::read_to_string(&source)
+                        .with_context(|| format!("Failed to read {}", source.display()))?
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "{} needs exactly one of template or source",
+                        file.path
+                    ));
+                }
+            };
+        }
+        let patches = pipeline
+            .ides
+            .values()
+            .flat_map(|ide| ide.patches.iter().chain(&ide.fpu.hard).chain(&ide.fpu.soft));
+        for patch in pipeline.headers.iter().chain(patches) {
+            validate(patch)?;
+        }
+        let ides = initializers::all();
+        for id in pipeline.ides.keys() {
+            if !ides.iter().any(|i| i.id() == id) {
+                warn!("Ignore patches for unknown IDE {} in pipeline", id);
+            }
+        }
+        Ok(pipeline)
+    }
+
+    /// 读取流水线文件
+    pub fn load(path: &Path) -> anyhow::Result<Pipeline> {
+        let path = env::current_dir()?.join(path);
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read pipeline {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut pipeline = Pipeline::parse(&content, dir)
+            .with_context(|| format!("Invalid pipeline {}", path.display()))?;
+        pipeline.path = Some(path);
+        Ok(pipeline)
+    }
+
+    /// 流水线文件路径，内置流水线为 `None`
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// IDE 的补丁（不含 FPU）
+    pub fn patches(&self, id: &str) -> &[Patch] {
+        self.ides.get(id).map_or(&[], |ide| &ide.patches)
+    }
+
+    /// IDE 配置 FPU 的补丁，替换其中的 `@math_define@` 和 `@mfpu@`
+    pub fn fpu_patches(&self, id: &str, fpu: FPUType) -> Vec<Patch> {
+        let Some(ide) = self.ides.get(id) else {
+            return Vec::new();
+        };
+        let patches = match fpu {
+            FPUType::Hard => &ide.fpu.hard,
+            FPUType::Soft => &ide.fpu.soft,
+        };
+        if patches.is_empty() {
+            return Vec::new();
+        }
+        // create 在 stm32tool.toml 中记录了芯片定义，其他项目从 .ioc 读取芯片系列
+        let chip = ProjectConfig::load().mcu.and_then(|mcu| {
+            let chip = creators::all()
+                .into_iter()
+                .find(|m| m.name().eq_ignore_ascii_case(&mcu))
+                .map(|m| m.info());
+            if chip.is_none() {
+                warn!("Unknown MCU {} in {}", mcu, PROJECT_FILE);
+            }
+            chip
+        });
+        let family = || {
+            find_ioc_file()
+                .and_then(IocFile::load)
+                .ok()
+                .and_then(|ioc| ioc.mcu().family)
+        };
+        let (math_define, mfpu) = hard_float_options(chip.as_ref(), family);
+        let vars = [
+            ("@math_define@", math_define.as_str()),
+            ("@mfpu@", mfpu.as_str()),
+        ];
+        patches.iter().map(|p| substitute(p, &vars)).collect()
+    }
+
+    /// 应用 IDE 的补丁和 FPU 补丁
+    pub fn apply(&self, id: &str, fpu: FPUType) -> anyhow::Result<PatchReport> {
+        let mut report = PatchReport::default();
+        for patch in self.patches(id).iter().chain(&self.fpu_patches(id, fpu)) {
+            report.apply(patch)?;
+        }
+        Ok(report)
+    }
+
+    /// 创建用户代码目录和文件，已存在的文件只在 `force` 时覆盖
+    pub fn generate_user_code(&self, ctx: &InitContext, force: bool) -> anyhow::Result<()> {
+        for dir in &self.directories {
+            dry_run::create_dir_all(dir)?;
+            info!("Created dir {}", dir);
+        }
+        for file in &self.templates {
+            render_file(&file.path, &file.content, ctx, force)
+                .with_context(|| format!("Invalid template {}", file.name()))?;
+        }
+        Ok(())
+    }
+
+    /// 缺少的用户代码目录和文件
+    pub fn missing_user_code(&self) -> Vec<&str> {
+        self.directories
+            .iter()
+            .chain(self.templates.iter().map(|f| &f.path))
+            .map(String::as_str)
+            .filter(|path| !dry_run::exists(path))
+            .collect()
+    }
+}
+
+static PIPELINE: OnceLock<Pipeline> = OnceLock::new();
+
+/// 选择流水线文件，`None` 时使用内置流水线
+pub fn set_pipeline(path: Option<PathBuf>) -> anyhow::Result<()> {
+    let pipeline = match path {
+        Some(path) => Pipeline::load(&path)?,
+        None => Pipeline::parse(DEFAULT_PIPELINE, Path::new("."))?,
+    };
+    PIPELINE
+        .set(pipeline)
+        .map_err(|_| anyhow!("Pipeline is already set"))
+}
+
+/// 当前使用的流水线
+pub fn current() -> &'static Pipeline {
+    PIPELINE.get_or_init(|| {
+        Pipeline::parse(DEFAULT_PIPELINE, Path::new(".")).expect("invalid built-in pipeline")
+    })
+}
+
+/// 内核对应的 CMSIS-DSP 宏
+fn math_define(core: &str) -> Option<&'static str> {
+    match core {
+        "Cortex-M4" => Some("ARM_MATH_CM4"),
+        "Cortex-M7" => Some("ARM_MATH_CM7"),
+        _ => None,
+    }
+}
+
+/// 选择 CMSIS-DSP 宏和 `-mfpu`：优先使用芯片定义中的内核和 FPU；没有芯片定义或其中
+/// 缺少这些信息时按芯片系列（`Mcu.Family`）选择，未知时按 Cortex-M4F 处理
+fn hard_float_options(
+    chip: Option<&McuInfo>,
+    family: impl FnOnce() -> Option<String>,
+) -> (String, String) {
+    if let Some(chip) = chip {
+        match (math_define(&chip.core), &chip.fpu) {
+            (Some(define), Some(fpu)) => return (define.to_string(), fpu.clone()),
+            _ => warn!(
+                "No hardware FPU settings for {} ({}), using the MCU family",
+                chip.name, chip.core
+            ),
+        }
+    }
+    let (define, fpu) = match family().as_deref() {
+        Some("STM32H7") => ("ARM_MATH_CM7", "fpv5-d16"),
+        Some("STM32F7") => ("ARM_MATH_CM7", "fpv5-sp-d16"),
+        _ => ("ARM_MATH_CM4", "fpv4-sp-d16"),
+    };
+    (define.to_string(), fpu.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patches::Occurrence;
+
+    fn parse(content: &str) -> anyhow::Result<Pipeline> {
+        Pipeline::parse(content, Path::new("."))
+    }
+
+    fn error(content: &str) -> String {
+        format!("{:#}", parse(content).unwrap_err())
+    }
+
+    fn append(file: &str, after: &str, insert: &str, marker: &str) -> Patch {
+        Patch::Append {
+            file: file.to_string(),
+            after: after.to_string(),
+            insert: insert.to_string(),
+            marker: marker.to_string(),
+            occurrence: Occurrence::All,
+        }
+    }
+
+    #[test]
+    fn template_needs_exactly_one_source() {
+        let both = "templates:\n  - {path: a.h, template: app.h.tmpl, source: a.tmpl}\n";
+        assert!(error(both).contains("a.h needs exactly one of template or source"));
+        let neither = "templates:\n  - {path: a.h}\n";
+        assert!(error(neither).contains("a.h needs exactly one of template or source"));
+        let unknown = "templates:\n  - {path: a.h, template: missing.tmpl}\n";
+        assert!(error(unknown).contains("Unknown built-in template missing.tmpl for a.h"));
+
+        // source 相对于流水线文件所在目录
+        let dir = env::temp_dir().join(format!("stm32tool-pipeline-{}", std::process::id()));
+        fs::create_dir_all(dir.join("tpl")).unwrap();
+        fs::write(dir.join("tpl/a.tmpl"), "// {author}\n").unwrap();
+        let pipeline = Pipeline::parse("templates:\n  - {path: a.h, source: tpl/a.tmpl}\n", &dir);
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(pipeline.unwrap().templates[0].content, "// {author}\n");
+        assert!(
+            error("templates:\n  - {path: a.h, source: tpl/a.tmpl}\n").contains("Failed to read")
+        );
+    }
+
+    #[test]
+    fn validates_regex_patterns() {
+        let patch = "{mode: regex_replace, file: a.c, pattern: '(', insert: x}";
+        for content in [
+            format!("headers:\n  - {patch}\n"),
+            format!("ides:\n  cmake:\n    patches:\n      - {patch}\n"),
+            format!("ides:\n  cmake:\n    fpu:\n      hard:\n        - {patch}\n"),
+            format!("ides:\n  clion:\n    fpu:\n      soft:\n        - {patch}\n"),
+        ] {
+            assert!(
+                error(&content).contains("Invalid pattern for a.c"),
+                "{}",
+                content
+            );
+        }
+        let valid =
+            "headers:\n  - {mode: regex_replace, file: a.c, pattern: '(\\d+)', insert: x}\n";
+        assert!(parse(valid).is_ok());
+    }
+
+    #[test]
+    fn ignores_unknown_ides() {
+        let pipeline = parse(
+            "ides:\n  keil:\n    patches:\n      - {mode: ensure_line, file: a, line: b}\n  \
+             cmake:\n    patches:\n      - {mode: ensure_line, file: c, line: d}\n",
+        )
+        .unwrap();
+        assert_eq!(pipeline.patches("cmake").len(), 1);
+        assert!(pipeline.patches("eide").is_empty());
+        assert!(pipeline.fpu_patches("eide", FPUType::Hard).is_empty());
+    }
+
+    /// 内置流水线与之前写在代码中的目录、模板和补丁一致
+    #[test]
+    fn default_pipeline_matches_old_layout() {
+        let pipeline = parse(DEFAULT_PIPELINE).unwrap();
+        assert_eq!(
+            pipeline.directories,
+            [
+                "UserCode/bsp",
+                "UserCode/drivers",
+                "UserCode/third_party",
+                "UserCode/libs",
+                "UserCode/interfaces",
+                "UserCode/controllers",
+                "UserCode/app",
+                "Modules",
+            ]
+        );
+        let templates: Vec<(&str, &str)> = pipeline
+            .templates
+            .iter()
+            .map(|f| (f.path.as_str(), f.content.as_str()))
+            .collect();
+        assert_eq!(
+            templates,
+            [
+                ("UserCode/app/app.h", include_str!("templates/app.h.tmpl")),
+                ("UserCode/app/app.c", include_str!("templates/app.c.tmpl")),
+                (
+                    "UserCode/README.md",
+                    include_str!("templates/README.md.tmpl")
+                ),
+            ]
+        );
+        assert_eq!(
+            pipeline.headers,
+            [
+                append(
+                    "CMakeLists_template.txt",
+                    "add_executable",
+                    "\n# 非侵入式引入头文件\ntarget_compile_options(${PROJECT_NAME}.elf PRIVATE -include ${CMAKE_SOURCE_DIR}/UserCode/app/app.h)\n",
+                    "UserCode/app/app.h",
+                ),
+                append(
+                    "Makefile",
+                    "CFLAGS += $(MCU)",
+                    "\n# 非侵入式引入头文件\nCFLAGS += -include UserCode/app/app.h\n",
+                    "UserCode/app/app.h",
+                ),
+            ]
+        );
+
+        assert_eq!(
+            pipeline.patches("cmake"),
+            [
+                append(
+                    "CMakeLists.txt",
+                    "# Add sources to executable",
+                    r#"file(GLOB_RECURSE SOURCES "UserCode/*.*")"#,
+                    r#"file(GLOB_RECURSE SOURCES "UserCode/*.*")"#,
+                ),
+                append(
+                    "CMakeLists.txt",
+                    "# Add user sources here",
+                    "    ${SOURCES}",
+                    "${SOURCES}",
+                ),
+                append(
+                    "CMakeLists.txt",
+                    "# Add include paths",
+                    "include_directories(UserCode)",
+                    "include_directories(UserCode)",
+                ),
+                append(
+                    "CMakeLists.txt",
+                    "list(REMOVE_ITEM CMAKE_C_IMPLICIT_LINK_LIBRARIES ob)",
+                    "\n# Add dependence from library\
+                     \n# ===================== DEPENDENCIES =====================\
+                     \n# e.g.\
+                     \n#add_subdirectory(library/motor_drivers/UserCode)\
+                     \n\
+                     \n# =======================================================",
+                    "# Add dependence from library",
+                ),
+            ]
+        );
+        let cmake = &pipeline.ides["cmake"].fpu;
+        let vars = [("@math_define@", "ARM_MATH_CM4"), ("@mfpu@", "fpv4-sp-d16")];
+        assert_eq!(
+            substitute(&cmake.hard[0], &vars),
+            append(
+                "CMakeLists.txt",
+                "set(CMAKE_C_EXTENSIONS ON)",
+                "\n#Uncomment for hardware floating point\n\
+                 add_compile_definitions(ARM_MATH_CM4;ARM_MATH_MATRIX_CHECK;ARM_MATH_ROUNDING)\n\
+                 add_compile_options(-mfloat-abi=hard -mfpu=fpv4-sp-d16)\n\
+                 add_link_options(-mfloat-abi=hard -mfpu=fpv4-sp-d16)\n\n\
+                 add_compile_options(-ffunction-sections -fdata-sections -fno-common -fmessage-length=0)\n",
+                "#Uncomment for hardware floating point",
+            )
+        );
+        assert_eq!(
+            cmake.soft,
+            [append(
+                "CMakeLists.txt",
+                "set(CMAKE_C_EXTENSIONS ON)",
+                "\n#Uncomment for software floating point\
+                 \nadd_compile_options(-mfloat-abi=soft)\
+                 \n\
+                 \nadd_compile_options(-ffunction-sections -fdata-sections -fno-common -fmessage-length=0)\n",
+                "#Uncomment for software floating point",
+            )]
+        );
+
+        let replace = |find: &str, insert: &str| Patch::Replace {
+            file: "CMakeLists_template.txt".to_string(),
+            find: find.to_string(),
+            insert: insert.to_string(),
+        };
+        assert_eq!(
+            pipeline.patches("clion"),
+            [
+                replace(
+                    "include_directories(${includes})",
+                    "include_directories(${includes} UserCode)"
+                ),
+                replace(
+                    "file(GLOB_RECURSE SOURCES ${sources})",
+                    r#"file(GLOB_RECURSE SOURCES ${sources} "UserCode/*.*")"#
+                ),
+            ]
+        );
+        // 取消所选浮点配置的注释，同时注释另一种
+        let file = || "CMakeLists_template.txt".to_string();
+        let uncomment = |marker: &str| Patch::UncommentBlock {
+            file: file(),
+            marker: marker.to_string(),
+        };
+        let comment = |marker: &str| Patch::CommentBlock {
+            file: file(),
+            marker: marker.to_string(),
+        };
+        let clion = &pipeline.ides["clion"].fpu;
+        let (hard, soft) = (
+            "#Uncomment for hardware floating point",
+            "#Uncomment for software floating point",
+        );
+        assert_eq!(clion.hard, [uncomment(hard), comment(soft)]);
+        assert_eq!(clion.soft, [uncomment(soft), comment(hard)]);
+        assert!(pipeline.patches("eide").is_empty());
+    }
+
+    #[test]
+    fn hard_float_follows_chip_definition() {
+        let chip = |name: &str| {
+            creators::all()
+                .into_iter()
+                .find(|m| m.name() == name)
+                .unwrap()
+                .info()
+        };
+        let options = |define: &str, fpu: &str| (define.to_string(), fpu.to_string());
+        let family = |family: &'static str| move || Some(family.to_string());
+
+        // 芯片定义优先于 .ioc 中的系列
+        assert_eq!(
+            hard_float_options(Some(&chip("STM32F407VETx")), family("STM32H7")),
+            options("ARM_MATH_CM4", "fpv4-sp-d16")
+        );
+        assert_eq!(
+            hard_float_options(Some(&chip("STM32H723VETx")), || None),
+            options("ARM_MATH_CM7", "fpv5-d16")
+        );
+        // 没有芯片定义或其中没有 FPU 时按系列选择
+        assert_eq!(
+            hard_float_options(None, family("STM32F7")),
+            options("ARM_MATH_CM7", "fpv5-sp-d16")
+        );
+        let mut no_fpu = chip("STM32H723VETx");
+        no_fpu.fpu = None;
+        assert_eq!(
+            hard_float_options(Some(&no_fpu), family("STM32F4")),
+            options("ARM_MATH_CM4", "fpv4-sp-d16")
+        );
+        assert_eq!(
+            hard_float_options(None, || None),
+            options("ARM_MATH_CM4", "fpv4-sp-d16")
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::dry_run;
+use crate::initializers::FPUType;
+use crate::stm32cubemx::Toolchain;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::warn;
+
+/// 项目配置文件，位于项目根目录，应提交到仓库
+pub const PROJECT_FILE: &str = "stm32tool.toml";
+
+/// `init` 的选择
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InitConfig {
+    /// 初始化的 IDE，如 `["cmake"]`
+    pub ides: Option<Vec<String>>,
+    pub fpu: Option<FPUType>,
+    pub skip_generate_user_code: Option<bool>,
+    pub skip_generate_clang_format: Option<bool>,
+    pub skip_non_intrusive_headers: Option<bool>,
+    /// init 流水线文件，省略时使用内置流水线
+    pub pipeline: Option<PathBuf>,
+}
+
+impl InitConfig {
+    /// 是否非侵入式引入 `UserCode/app/app.h`
+    pub fn non_intrusive_headers(&self) -> bool {
+        !self.skip_generate_user_code.unwrap_or(false)
+            && !self.skip_non_intrusive_headers.unwrap_or(false)
+    }
+
+    /// 未设置的项使用 `fallback` 中的值
+    pub fn or(&self, fallback: &InitConfig) -> InitConfig {
+        InitConfig {
+            ides: self.ides.clone().or_else(|| fallback.ides.clone()),
+            fpu: self.fpu.or(fallback.fpu),
+            skip_generate_user_code: self
+                .skip_generate_user_code
+                .or(fallback.skip_generate_user_code),
+            skip_generate_clang_format: self
+                .skip_generate_clang_format
+                .or(fallback.skip_generate_clang_format),
+            skip_non_intrusive_headers: self
+                .skip_non_intrusive_headers
+                .or(fallback.skip_non_intrusive_headers),
+            pipeline: self.pipeline.clone().or_else(|| fallback.pipeline.clone()),
+        }
+    }
+}
+
+/// 项目配置 `stm32tool.toml`，记录 `create`/`init` 的选择，命令行参数优先
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ProjectConfig {
+    /// 芯片定义名，如 `STM32F407VETx`
+    pub mcu: Option<String>,
+    /// 板级配置名
+    pub board: Option<String>,
+    pub toolchain: Option<Toolchain>,
+    #[serde(default)]
+    pub init: InitConfig,
+}
+
+impl ProjectConfig {
+    /// 读取当前目录下的项目配置，不存在或格式错误时使用默认值
+    pub fn load() -> ProjectConfig {
+        let Ok(content) = dry_run::read_to_string(PROJECT_FILE) else {
+            return ProjectConfig::default();
+        };
+        toml::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignore invalid {}: {}", PROJECT_FILE, e);
+            ProjectConfig::default()
+        })
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let content = format!(
+            "# stm32tool 项目配置，由 create/init/generate 更新，命令行参数优先\n{}",
+            toml::to_string(self)?
+        );
+        if dry_run::read_to_string(PROJECT_FILE).ok().as_deref() != Some(content.as_str()) {
+            dry_run::write(PROJECT_FILE, &content)?;
+        }
+        Ok(())
+    }
+}
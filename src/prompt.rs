@@ -0,0 +1,62 @@
+use anyhow::{Result, anyhow};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, MultiSelect, Select};
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// `--yes`：确认提示一律回答是
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+/// `--no-input`：需要交互时直接报错
+static NO_INPUT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_mode(yes: bool, no_input: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+    NO_INPUT.store(no_input, Ordering::Relaxed);
+}
+
+/// 无法交互时返回错误，提示应使用的参数 `hint`
+fn ensure_interactive(prompt: &str, hint: &str) -> Result<()> {
+    if NO_INPUT.load(Ordering::Relaxed) {
+        return Err(anyhow!(
+            "\"{}\" needs input but --no-input is set, use {}",
+            prompt,
+            hint
+        ));
+    }
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "\"{}\" needs input but stdin is not a terminal, use {}",
+            prompt,
+            hint
+        ));
+    }
+    Ok(())
+}
+
+pub fn select(prompt: &str, items: &[String], hint: &str) -> Result<usize> {
+    ensure_interactive(prompt, hint)?;
+    Ok(Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(items)
+        .interact()?)
+}
+
+pub fn multi_select(prompt: &str, items: &[&str], hint: &str) -> Result<Vec<usize>> {
+    ensure_interactive(prompt, hint)?;
+    Ok(MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(items)
+        .interact()?)
+}
+
+/// 确认提示，`--yes` 时直接返回 `true`
+pub fn confirm(prompt: &str, default: bool) -> Result<bool> {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+    ensure_interactive(prompt, "--yes")?;
+    Ok(Confirm::new()
+        .with_prompt(prompt)
+        .default(default)
+        .interact()?)
+}
@@ -1,37 +1,66 @@
+use crate::config;
+use crate::dry_run;
+use crate::utils::{get_author, get_email};
+use anyhow::Context;
+use chrono::Local;
 use serde::Serialize;
-use std::fs;
 use std::path::Path;
 use tinytemplate::TinyTemplate;
 use tracing::warn;
 
+/// init 渲染模板使用的上下文
+#[derive(Serialize)]
+pub struct InitContext {
+    author: String,
+    email: String,
+    date: String,
+    year: String,
+}
+
+impl InitContext {
+    /// 作者和邮箱优先使用 profile 中的设置
+    pub fn new() -> InitContext {
+        let now = Local::now();
+        let profile = config::profile();
+        InitContext {
+            author: profile.author.clone().unwrap_or_else(get_author),
+            email: profile.email.clone().unwrap_or_else(get_email),
+            date: now.format("%Y-%m-%d").to_string(),
+            year: now.format("%Y").to_string(),
+        }
+    }
+}
+
 pub fn render_file<T: Serialize>(
     path: &str,
     template: &str,
     ctx: &T,
     force: bool,
-) -> std::io::Result<()> {
-    if Path::new(path).exists() && !force {
+) -> anyhow::Result<()> {
+    if dry_run::exists(path) && !force {
         warn!("Skip existing {}", path);
         return Ok(());
     }
 
     if let Some(parent) = Path::new(path).parent() {
-        fs::create_dir_all(parent)?;
+        dry_run::create_dir_all(parent)?;
     }
 
     // 渲染模板
-    let content = render_string(template, ctx)?;
+    let content =
+        render_string(template, ctx).with_context(|| format!("Failed to render {}", path))?;
 
-    fs::write(path, content)?;
+    dry_run::write(path, &content)?;
     Ok(())
 }
 
-pub fn render_string<T: Serialize>(template: &str, ctx: &T) -> std::io::Result<String> {
+/// 渲染模板文本，模板语法错误或引用了不存在的字段时返回错误
+pub fn render_string<T: Serialize>(template: &str, ctx: &T) -> anyhow::Result<String> {
     let mut tt = TinyTemplate::new();
-    tt.add_template("tpl", template).unwrap();
+    tt.add_template("tpl", template)?;
 
     // 渲染模板
-    let content = tt.render("tpl", ctx).unwrap();
+    let content = tt.render("tpl", ctx)?;
 
     Ok(content)
 }
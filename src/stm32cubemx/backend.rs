@@ -0,0 +1,325 @@
+use crate::stm32cubemx::discovery::{self, CubeMxVersion, Installation};
+use crate::stm32cubemx::script::CubeMxScript;
+use crate::stm32cubemx::{output, process};
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use std::{env, fs};
+use tracing::{error, info, warn};
+
+/// 执行 CubeMX 脚本的方式
+pub trait CubeMxBackend: Send + Sync {
+    /// 在当前目录执行脚本
+    fn run(&self, script: &CubeMxScript) -> Result<()>;
+
+    /// 使用的 CubeMX 版本，无法确定时为 `None`
+    fn version(&self) -> Option<CubeMxVersion> {
+        None
+    }
+
+    /// 使用的 CubeMX 安装，不需要 CubeMX 时为 `None`
+    fn installation(&self) -> Option<Result<Installation>> {
+        None
+    }
+}
+
+static BACKEND: OnceLock<Box<dyn CubeMxBackend>> = OnceLock::new();
+
+/// 按 `--cubemx` 选择后端：
+/// - `fixture:<dir>` 从 `<dir>` 中回放预先录制的生成结果，不需要安装 CubeMX
+/// - `record:<dir>` 运行 CubeMX，并把生成的文件录制到 `<dir>`
+/// - 其他值为 CubeMX 可执行文件路径
+pub fn set_backend(spec: Option<String>) -> Result<()> {
+    // `create` 会切换到工程目录，这里先转为绝对路径
+    let absolute = |dir: &str| env::current_dir().map(|cwd| cwd.join(dir));
+    let backend: Box<dyn CubeMxBackend> = match spec {
+        Some(spec) => match spec.split_once(':') {
+            Some(("fixture", dir)) => Box::new(FixtureBackend {
+                dir: absolute(dir)?,
+            }),
+            Some(("record", dir)) => Box::new(RecordBackend {
+                dir: absolute(dir)?,
+                inner: ProcessBackend { executable: None },
+            }),
+            _ => Box::new(ProcessBackend {
+                executable: Some(spec),
+            }),
+        },
+        None => Box::new(ProcessBackend { executable: None }),
+    };
+    BACKEND
+        .set(backend)
+        .map_err(|_| anyhow!("CubeMX backend is already set"))
+}
+
+/// 当前使用的后端，未设置时运行系统中的 CubeMX
+pub fn current() -> &'static dyn CubeMxBackend {
+    BACKEND
+        .get_or_init(|| Box::new(ProcessBackend { executable: None }))
+        .as_ref()
+}
+
+/// 启动 STM32CubeMX 进程执行脚本
+pub struct ProcessBackend {
+    /// CubeMX 可执行文件或安装目录，未指定时自动查找
+    executable: Option<String>,
+}
+
+impl CubeMxBackend for ProcessBackend {
+    fn run(&self, script: &CubeMxScript) -> Result<()> {
+        // generate tmp file in system temp directory, removed when dropped
+        let temp_script = process::TempScript::create(&script.to_string())?;
+        let tmp_path_str = temp_script
+            .path()
+            .to_str()
+            .expect("failed to convert path to string");
+        let installation = discovery::discover(self.executable.as_deref())?;
+        let mut command = installation.command(tmp_path_str);
+
+        // 捕获 stdout/stderr，写入日志
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let mut child = match process::spawn(
+            command
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        ) {
+            Ok(child) => child,
+            Err(e) => {
+                error!(
+                    "Failed to execute stm32cubemx from {}: {}",
+                    installation.source, e
+                );
+                return Err(anyhow!("Failed to execute stm32cubemx: {}", e));
+            }
+        };
+        let readers = [
+            child
+                .stdout
+                .take()
+                .map(|r| output::capture(r, lines.clone())),
+            child
+                .stderr
+                .take()
+                .map(|r| output::capture(r, lines.clone())),
+        ];
+        let status = process::wait(&mut child);
+        for reader in readers.into_iter().flatten() {
+            let _ = reader.join();
+        }
+
+        let lines = lines.lock().unwrap();
+        let log = output::write_log(&script.to_string(), &lines)?;
+
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => {
+                error!("{}", e);
+                error!("CubeMX output saved to {}", log.display());
+                return Err(e);
+            }
+        };
+
+        // handle result
+        if status.success() {
+            // CubeMX 遇到部分错误（如无法识别的命令）时退出码仍为 0
+            let diagnostics = output::diagnose(&lines);
+            if !diagnostics.is_empty() {
+                for diagnostic in diagnostics {
+                    warn!("{}", diagnostic);
+                }
+                warn!("CubeMX output saved to {}", log.display());
+            }
+            Ok(())
+        } else {
+            error!("Run script failed with status: {}", status);
+            for diagnostic in output::diagnose(&lines) {
+                error!("{}", diagnostic);
+            }
+            error!("CubeMX output saved to {}", log.display());
+            Err(anyhow!(
+                "Run script failed with status: {}, see {}",
+                status,
+                log.display()
+            ))
+        }
+    }
+
+    fn version(&self) -> Option<CubeMxVersion> {
+        self.installation()?.ok()?.version
+    }
+
+    fn installation(&self) -> Option<Result<Installation>> {
+        Some(discovery::discover(self.executable.as_deref()))
+    }
+}
+
+/// 录制和回放时不处理的目录
+const SKIP_DIRS: [&str; 2] = [".git", ".stm32tool"];
+
+/// 去掉注释，参数中的工程路径替换为占位符，使录制结果与模板注释和工程所在位置无关。
+/// 替换在加引号之前进行，路径中有空格时结果也相同
+fn fixture_key(script: &CubeMxScript, project_dir: &Path) -> (String, String) {
+    let dir = project_dir.to_string_lossy().to_string();
+    let text = script
+        .without_comments()
+        .map_args(|arg| {
+            arg.replace(&dir, "$PROJECT")
+                .replace(&dir.replace('\\', "/"), "$PROJECT")
+                .replace("$PROJECT\\", "$PROJECT/")
+        })
+        .to_string();
+    // FNV-1a，结果在不同平台和 Rust 版本间保持一致
+    let hash = text.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    (format!("{:016x}", hash), text)
+}
+
+/// 列出目录下的所有文件（相对路径），跳过 `SKIP_DIRS`
+fn list_files(root: &Path) -> Result<Vec<PathBuf>> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                let skip = path
+                    .file_name()
+                    .is_some_and(|n| SKIP_DIRS.iter().any(|s| n == *s));
+                if !skip {
+                    walk(root, &path, files)?;
+                }
+            } else {
+                files.push(path.strip_prefix(root)?.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+    let mut files = Vec::new();
+    if root.is_dir() {
+        walk(root, root, &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(from, to)?;
+    Ok(())
+}
+
+/// 回放录制的生成结果：把 `<dir>/<脚本哈希>/` 下的文件复制到当前目录
+pub struct FixtureBackend {
+    dir: PathBuf,
+}
+
+impl CubeMxBackend for FixtureBackend {
+    fn run(&self, script: &CubeMxScript) -> Result<()> {
+        let (key, text) = fixture_key(script, &env::current_dir()?);
+        let fixture = self.dir.join(&key);
+        if !fixture.is_dir() {
+            error!("No fixture for script:\n{}", text);
+            return Err(anyhow!(
+                "Fixture {} not found, record it with --cubemx record:{}",
+                fixture.display(),
+                self.dir.display()
+            ));
+        }
+        let files = list_files(&fixture)?;
+        for file in &files {
+            copy_file(&fixture.join(file), file)?;
+        }
+        info!("Replayed {} files from {}", files.len(), fixture.display());
+        Ok(())
+    }
+}
+
+/// 运行 CubeMX，把新增或修改的文件录制为 `<dir>/<脚本哈希>/`，脚本保存为 `<dir>/<脚本哈希>.cubemx`
+pub struct RecordBackend {
+    dir: PathBuf,
+    inner: ProcessBackend,
+}
+
+impl RecordBackend {
+    fn snapshot() -> Result<HashMap<PathBuf, (u64, Option<SystemTime>)>> {
+        list_files(Path::new("."))?
+            .into_iter()
+            .map(|file| {
+                let meta = fs::metadata(&file)?;
+                Ok((file, (meta.len(), meta.modified().ok())))
+            })
+            .collect()
+    }
+}
+
+impl CubeMxBackend for RecordBackend {
+    fn run(&self, script: &CubeMxScript) -> Result<()> {
+        let (key, text) = fixture_key(script, &env::current_dir()?);
+        let before = Self::snapshot()?;
+        self.inner.run(script)?;
+        let after = Self::snapshot()?;
+
+        let fixture = self.dir.join(&key);
+        if fixture.exists() {
+            fs::remove_dir_all(&fixture)?;
+        }
+        fs::create_dir_all(&fixture)?;
+        let mut count = 0;
+        for (file, state) in &after {
+            if before.get(file) != Some(state) {
+                copy_file(file, &fixture.join(file))?;
+                count += 1;
+            }
+        }
+        fs::write(self.dir.join(format!("{}.cubemx", key)), text)?;
+        info!("Recorded {} files to {}", count, fixture.display());
+        Ok(())
+    }
+
+    fn version(&self) -> Option<CubeMxVersion> {
+        self.inner.version()
+    }
+
+    fn installation(&self) -> Option<Result<Installation>> {
+        self.inner.installation()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(text: &str, dir: &str) -> (String, String) {
+        fixture_key(&CubeMxScript::parse(text).unwrap(), Path::new(dir))
+    }
+
+    #[test]
+    fn fixture_key_ignores_comments_and_project_dir() {
+        let script = "load STM32F407VETx\n\
+            # 配置时钟\n\
+            project path \"{dir}\"\n\
+            config saveas {dir}/demo.ioc\n\
+            exit";
+        let (plain, text) = key(&script.replace("{dir}", "/tmp/a"), "/tmp/a");
+        assert_eq!(
+            text,
+            "load STM32F407VETx\nproject path \"$PROJECT\"\nconfig saveas $PROJECT/demo.ioc\nexit"
+        );
+        // 修改注释
+        let edited = script.replace("配置时钟", "configure clocks");
+        assert_eq!(key(&edited.replace("{dir}", "/tmp/a"), "/tmp/a").0, plain);
+        // 路径中有空格时渲染会加引号，但占位符替换在加引号之前
+        let spaced = "/tmp/my project";
+        let quoted = script.replace("{dir}/demo.ioc", "\"{dir}/demo.ioc\"");
+        assert_eq!(key(&quoted.replace("{dir}", spaced), spaced).0, plain);
+        // Windows 路径
+        let windows = "C:\\Users\\me\\demo";
+        assert_eq!(key(&script.replace("{dir}", windows), windows).0, plain);
+        assert_ne!(key(&script.replace("{dir}", "/tmp/a"), "/tmp/b").0, plain);
+    }
+}
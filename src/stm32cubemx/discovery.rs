@@ -0,0 +1,395 @@
+use crate::config::UserConfig;
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs};
+
+/// 安装目录中可能记录版本号的文件
+const VERSION_FILES: [&str; 3] = [".installationinformation", "version.txt", "VERSION"];
+
+/// STM32CubeMX 版本号，如 `6.12.0`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CubeMxVersion(Vec<u32>);
+
+impl CubeMxVersion {
+    pub fn parse(text: &str) -> Option<CubeMxVersion> {
+        let parts = text
+            .trim()
+            .split('.')
+            .map(|p| p.parse().ok())
+            .collect::<Option<Vec<u32>>>()?;
+        (!parts.is_empty()).then_some(CubeMxVersion(parts))
+    }
+}
+
+impl Ord for CubeMxVersion {
+    /// 缺少的部分按 0 比较，`6.12` 与 `6.12.0` 相等
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.0.len().max(other.0.len());
+        (0..len)
+            .map(|i| {
+                let a = self.0.get(i).copied().unwrap_or(0);
+                let b = other.0.get(i).copied().unwrap_or(0);
+                a.cmp(&b)
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for CubeMxVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for CubeMxVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}", parts.join("."))
+    }
+}
+
+/// 找到的 STM32CubeMX
+#[derive(Debug)]
+pub struct Installation {
+    program: PathBuf,
+    /// 脚本参数之前的参数，使用 JRE 启动时为 `-jar <STM32CubeMX>`
+    prefix_args: Vec<OsString>,
+    pub version: Option<CubeMxVersion>,
+    /// 从哪里找到的，用于提示
+    pub source: String,
+}
+
+impl Installation {
+    /// 以无界面模式运行脚本的命令
+    pub fn command(&self, script_path: &str) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.prefix_args)
+            .arg("-s")
+            .arg(script_path)
+            .arg("-q");
+        command
+    }
+
+    /// 启动 CubeMX 使用的 java，直接运行启动器时为 `None`
+    pub fn java(&self) -> Option<&Path> {
+        (!self.prefix_args.is_empty()).then_some(self.program.as_path())
+    }
+}
+
+/// 从安装目录中的文件或目录名读取版本号
+fn read_version(dir: &Path) -> Option<CubeMxVersion> {
+    let pattern = Regex::new(r"(?i)(?:version|cubemx)\D{0,16}?(\d+\.\d+(?:\.\d+)?)").unwrap();
+    let find = |text: &str| {
+        pattern
+            .captures(text)
+            .and_then(|c| CubeMxVersion::parse(&c[1]))
+    };
+    VERSION_FILES
+        .iter()
+        .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+        .find_map(|content| find(&content))
+        .or_else(|| find(&dir.file_name()?.to_string_lossy()))
+}
+
+/// 检查目录是否为 CubeMX 安装目录，优先使用自带的 JRE
+fn from_dir(dir: &Path, source: &str) -> Option<Installation> {
+    let windows = cfg!(target_os = "windows");
+    let launcher = dir.join(if windows {
+        "STM32CubeMX.exe"
+    } else {
+        "STM32CubeMX"
+    });
+    if !launcher.is_file() {
+        return None;
+    }
+    let java = dir
+        .join("jre")
+        .join("bin")
+        .join(if windows { "java.exe" } else { "java" });
+    let (program, prefix_args) = if java.is_file() {
+        (java, vec!["-jar".into(), launcher.into()])
+    } else if windows {
+        // STM32CubeMX.exe 实际是 jar，需要用 java 启动
+        (PathBuf::from("java"), vec!["-jar".into(), launcher.into()])
+    } else {
+        (launcher, Vec::new())
+    };
+    Some(Installation {
+        program,
+        prefix_args,
+        version: read_version(dir),
+        source: format!("{} ({})", source, dir.display()),
+    })
+}
+
+/// 在 `path`（格式同环境变量 PATH）中查找可执行文件
+fn find_on_path(name: &str, path: &OsStr) -> Option<PathBuf> {
+    env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// 常见的安装位置，同一目录下的多个版本按名称倒序，优先使用较新的
+fn common_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join("STM32CubeMX"));
+        dirs.push(home.join("STM32Cube").join("STM32CubeMX"));
+    }
+    if cfg!(target_os = "windows") {
+        dirs.push(PathBuf::from(
+            r"C:\Program Files\STMicroelectronics\STM32Cube\STM32CubeMX",
+        ));
+    } else {
+        for parent in ["/opt/st", "/opt"] {
+            let Ok(entries) = fs::read_dir(parent) else {
+                continue;
+            };
+            let mut found: Vec<PathBuf> = entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.file_name().is_some_and(|n| {
+                        n.to_string_lossy()
+                            .to_lowercase()
+                            .starts_with("stm32cubemx")
+                    })
+                })
+                .collect();
+            found.sort();
+            dirs.extend(found.into_iter().rev());
+        }
+        dirs.push(PathBuf::from(
+            "/usr/local/STMicroelectronics/STM32Cube/STM32CubeMX",
+        ));
+        dirs.push(PathBuf::from(
+            "/Applications/STMicroelectronics/STM32CubeMX.app/Contents/MacOs",
+        ));
+    }
+    dirs
+}
+
+/// 自动查找时依次检查的位置
+struct SearchPaths {
+    /// 环境变量 `STM32CubeMX_dir`
+    env_dir: Option<String>,
+    /// 用户配置中的 `[cubemx] dir`
+    config_dir: Option<PathBuf>,
+    /// 环境变量 PATH
+    path: Option<OsString>,
+    common_dirs: Vec<PathBuf>,
+}
+
+/// 查找 STM32CubeMX，依次检查：`--cubemx` 指定的路径、环境变量 `STM32CubeMX_dir`、
+/// 用户配置中的 `[cubemx] dir`、PATH 中的 `stm32cubemx`、常见安装位置
+pub fn discover(executable: Option<&str>) -> Result<Installation> {
+    if let Some(executable) = executable {
+        let path = PathBuf::from(executable);
+        if path.is_dir() {
+            return from_dir(&path, "--cubemx")
+                .ok_or_else(|| anyhow!("{} is not a STM32CubeMX installation", executable));
+        }
+        let version = path
+            .canonicalize()
+            .ok()
+            .and_then(|p| read_version(p.parent()?));
+        return Ok(Installation {
+            program: path,
+            prefix_args: Vec::new(),
+            version,
+            source: "--cubemx".to_string(),
+        });
+    }
+
+    search(SearchPaths {
+        env_dir: env::var("STM32CubeMX_dir").ok(),
+        config_dir: UserConfig::load().cubemx.dir,
+        path: env::var_os("PATH"),
+        common_dirs: common_dirs(),
+    })
+}
+
+fn search(paths: SearchPaths) -> Result<Installation> {
+    let mut checked = Vec::new();
+    if let Some(dir) = paths.env_dir {
+        if let Some(found) = from_dir(Path::new(&dir), "STM32CubeMX_dir") {
+            return Ok(found);
+        }
+        checked.push(format!("STM32CubeMX_dir={}", dir));
+    }
+    if let Some(dir) = paths.config_dir {
+        if let Some(found) = from_dir(&dir, "config") {
+            return Ok(found);
+        }
+        checked.push(format!("config cubemx.dir={}", dir.display()));
+    }
+    if !cfg!(target_os = "windows")
+        && let Some(path) = paths
+            .path
+            .and_then(|path| find_on_path("stm32cubemx", &path))
+    {
+        let version = path
+            .canonicalize()
+            .ok()
+            .and_then(|p| read_version(p.parent()?));
+        return Ok(Installation {
+            program: path,
+            prefix_args: Vec::new(),
+            version,
+            source: "PATH".to_string(),
+        });
+    }
+    checked.push("stm32cubemx on PATH".to_string());
+    for dir in paths.common_dirs {
+        if let Some(found) = from_dir(&dir, "default location") {
+            return Ok(found);
+        }
+        checked.push(dir.display().to_string());
+    }
+    Err(anyhow!(
+        "STM32CubeMX not found, set STM32CubeMX_dir to the installation directory. Checked: {}",
+        checked.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试用的临时目录，结束时删除
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = env::temp_dir().join(format!(
+                "stm32tool-discovery-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        /// 创建文件及其所在目录，返回文件路径
+        fn file(&self, path: &str, content: &str) -> PathBuf {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            path
+        }
+
+        /// 创建包含启动器和版本文件的安装目录
+        fn installation(&self, name: &str, version: &str) -> PathBuf {
+            let launcher = if cfg!(target_os = "windows") {
+                "STM32CubeMX.exe"
+            } else {
+                "STM32CubeMX"
+            };
+            self.file(&format!("{}/{}", name, launcher), "");
+            self.file(
+                &format!("{}/.installationinformation", name),
+                &format!("STM32CubeMX Version {}\n", version),
+            );
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn version(text: &str) -> CubeMxVersion {
+        CubeMxVersion::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parses_and_compares_versions() {
+        assert_eq!(version(" 6.12.1\n").to_string(), "6.12.1");
+        assert_eq!(CubeMxVersion::parse("6.x"), None);
+        assert_eq!(CubeMxVersion::parse(""), None);
+        // 按数值而不是字符串比较，缺少的部分按 0
+        assert!(version("6.9.2") < version("6.12"));
+        assert_eq!(version("6.12").cmp(&version("6.12.0")), Ordering::Equal);
+        assert!(version("6.11.1") < version("6.12.0"));
+    }
+
+    #[test]
+    fn reads_version_from_files_or_dir_name() {
+        let tmp = TempDir::new("version");
+        tmp.file("a/version.txt", "STM32CubeMX version: 6.10.0");
+        assert_eq!(read_version(&tmp.0.join("a")), Some(version("6.10.0")));
+        fs::create_dir_all(tmp.0.join("STM32CubeMX-6.8.1")).unwrap();
+        assert_eq!(
+            read_version(&tmp.0.join("STM32CubeMX-6.8.1")),
+            Some(version("6.8.1"))
+        );
+        tmp.file("b/VERSION", "unknown");
+        assert_eq!(read_version(&tmp.0.join("b")), None);
+    }
+
+    #[test]
+    fn prefers_bundled_jre() {
+        let tmp = TempDir::new("jre");
+        let dir = tmp.installation("cubemx", "6.12.0");
+        let found = from_dir(&dir, "test").unwrap();
+        assert_eq!(found.version, Some(version("6.12.0")));
+        if !cfg!(target_os = "windows") {
+            assert_eq!(found.java(), None);
+        }
+
+        let java = if cfg!(target_os = "windows") {
+            "java.exe"
+        } else {
+            "java"
+        };
+        let java = tmp.file(&format!("cubemx/jre/bin/{}", java), "");
+        let found = from_dir(&dir, "test").unwrap();
+        assert_eq!(found.java(), Some(java.as_path()));
+        assert_eq!(found.prefix_args[0], "-jar");
+        assert!(from_dir(&tmp.0, "test").is_none());
+    }
+
+    #[test]
+    fn searches_in_order() {
+        let tmp = TempDir::new("order");
+        let env_dir = tmp.installation("env", "6.1");
+        let config_dir = tmp.installation("config", "6.2");
+        tmp.file("bin/stm32cubemx", "");
+        let common = tmp.installation("common", "6.4");
+        let paths = |env_dir: &Path, config_dir: &Path, bin: &str| SearchPaths {
+            env_dir: Some(env_dir.to_string_lossy().to_string()),
+            config_dir: Some(config_dir.to_path_buf()),
+            path: Some(tmp.0.join(bin).into_os_string()),
+            common_dirs: vec![tmp.0.join("missing"), common.clone()],
+        };
+        let missing = tmp.0.join("missing");
+        let source = |paths| search(paths).unwrap().source;
+
+        assert!(source(paths(&env_dir, &config_dir, "bin")).starts_with("STM32CubeMX_dir"));
+        assert!(source(paths(&missing, &config_dir, "bin")).starts_with("config"));
+        if !cfg!(target_os = "windows") {
+            assert_eq!(source(paths(&missing, &missing, "bin")), "PATH");
+        }
+        assert!(source(paths(&missing, &missing, "none")).starts_with("default location"));
+
+        let error = search(SearchPaths {
+            env_dir: None,
+            config_dir: None,
+            path: None,
+            common_dirs: vec![missing.clone()],
+        })
+        .unwrap_err()
+        .to_string();
+        assert!(error.ends_with(&format!("stm32cubemx on PATH, {}", missing.display())));
+    }
+}
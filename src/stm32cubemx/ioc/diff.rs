@@ -0,0 +1,283 @@
+use crate::stm32cubemx::ioc::{
+    INDEXED_LISTS, IocFile, is_indexed_list_key, is_pin_name, unescape_value,
+};
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// 变更所属的分组
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IocGroup {
+    /// `Mcu.*`、`MxCube.*` 等芯片和文件信息
+    Mcu,
+    /// `ProjectManager.*`
+    Project,
+    /// `RCC.*`
+    Clock,
+    /// `FREERTOS.*` 及其虚拟引脚
+    Rtos,
+    /// 其余 IP，如 `USART1`、`NVIC`
+    Peripheral(String),
+    /// 引脚，如 `PA9`
+    Pin(String),
+}
+
+impl fmt::Display for IocGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IocGroup::Mcu => write!(f, "MCU"),
+            IocGroup::Project => write!(f, "Project"),
+            IocGroup::Clock => write!(f, "Clock"),
+            IocGroup::Rtos => write!(f, "RTOS"),
+            IocGroup::Peripheral(name) => write!(f, "Peripheral {}", name),
+            IocGroup::Pin(name) => write!(f, "Pin {}", name),
+        }
+    }
+}
+
+/// 单个键的变更，值已还原转义
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IocChange {
+    /// 组内的名称，如 `PA9.Signal` 在 `Pin PA9` 组中为 `Signal`
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// 确定键所属分组及组内名称
+pub fn classify(key: &str) -> (IocGroup, String) {
+    let Some((prefix, rest)) = key.split_once('.') else {
+        return (IocGroup::Mcu, key.to_string());
+    };
+    let group = match prefix {
+        "Mcu" | "MxCube" | "MxDb" | "File" | "CAD" | "PinOutPanel" => IocGroup::Mcu,
+        "ProjectManager" => IocGroup::Project,
+        "RCC" => IocGroup::Clock,
+        "FREERTOS" => IocGroup::Rtos,
+        _ if is_pin_name(prefix) => IocGroup::Pin(prefix.to_string()),
+        _ => {
+            if let Some(vp) = prefix.strip_prefix("VP_") {
+                // 虚拟引脚，如 VP_SYS_VS_tim7、VP_FREERTOS_VS_CMSIS_V2
+                let ip = vp.split('_').next().unwrap_or(vp);
+                let group = if ip == "FREERTOS" {
+                    IocGroup::Rtos
+                } else {
+                    IocGroup::Peripheral(ip.to_string())
+                };
+                return (group, key.to_string());
+            }
+            IocGroup::Peripheral(prefix.to_string())
+        }
+    };
+    (group, rest.to_string())
+}
+
+/// 按分组比较两个 .ioc 文件，忽略注释（时间戳）、键顺序和编号列表的重新编号
+pub fn diff(old: &IocFile, new: &IocFile) -> BTreeMap<IocGroup, Vec<IocChange>> {
+    let collect = |ioc: &IocFile| -> BTreeMap<String, String> {
+        ioc.entries()
+            .filter(|(k, _)| !is_indexed_list_key(k))
+            .map(|(k, v)| (k.to_string(), unescape_value(v)))
+            .collect()
+    };
+    let old_entries = collect(old);
+    let new_entries = collect(new);
+
+    let mut groups: BTreeMap<IocGroup, Vec<IocChange>> = BTreeMap::new();
+    let keys: BTreeSet<&String> = old_entries.keys().chain(new_entries.keys()).collect();
+    for key in keys {
+        let old_value = old_entries.get(key);
+        let new_value = new_entries.get(key);
+        if old_value == new_value {
+            continue;
+        }
+        let (group, name) = classify(key);
+        groups.entry(group).or_default().push(IocChange {
+            name,
+            old: old_value.cloned(),
+            new: new_value.cloned(),
+        });
+    }
+
+    for (prefix, _) in INDEXED_LISTS {
+        let name = prefix.trim_start_matches("Mcu.");
+        let old_items: BTreeSet<String> = old.indexed_list(prefix).into_iter().collect();
+        let new_items: BTreeSet<String> = new.indexed_list(prefix).into_iter().collect();
+        for item in old_items.difference(&new_items) {
+            groups.entry(IocGroup::Mcu).or_default().push(IocChange {
+                name: name.to_string(),
+                old: Some(item.clone()),
+                new: None,
+            });
+        }
+        for item in new_items.difference(&old_items) {
+            groups.entry(IocGroup::Mcu).or_default().push(IocChange {
+                name: name.to_string(),
+                old: None,
+                new: Some(item.clone()),
+            });
+        }
+    }
+    groups
+}
+
+/// 输出分组后的差异
+pub fn print_diff(groups: &BTreeMap<IocGroup, Vec<IocChange>>) {
+    for (group, changes) in groups {
+        println!("[{}]", group);
+        for change in changes {
+            match (&change.old, &change.new) {
+                (None, Some(new)) => println!("  + {} = {}", change.name, new),
+                (Some(old), None) => println!("  - {} = {}", change.name, old),
+                (Some(old), Some(new)) => println!("  ~ {}: {} -> {}", change.name, old, new),
+                (None, None) => {}
+            }
+        }
+    }
+}
+
+/// `ioc diff` 入口
+///
+/// 参数为 `<a> <b>`，或 git external diff 传入的
+/// `<path> <old-file> <old-hex> <old-mode> <new-file> <new-hex> <new-mode>`
+pub fn run_diff(files: &[String]) -> Result<()> {
+    let (path, old_file, new_file) = match files {
+        [old_file, new_file] => (None, old_file, new_file),
+        [path, old_file, _, _, new_file, _, _] => (Some(path), old_file, new_file),
+        _ => {
+            return Err(anyhow!(
+                "Expected <a> <b> or the 7 arguments of a git external diff, got {} arguments",
+                files.len()
+            ));
+        }
+    };
+    let old = IocFile::load(old_file)?;
+    let new = IocFile::load(new_file)?;
+    let groups = diff(&old, &new);
+    if groups.is_empty() {
+        return Ok(());
+    }
+    match path {
+        Some(path) => println!("ioc diff a/{} b/{}", path, path),
+        None => println!("ioc diff {} {}", old_file, new_file),
+    }
+    print_diff(&groups);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "#MicroXplorer Configuration settings - do not modify\n\
+        #Fri Oct 17 10:00:00 CST 2026\n\
+        FREERTOS.Tasks01=defaultTask,24,128\n\
+        Mcu.IP0=RCC\n\
+        Mcu.IP1=USART1\n\
+        Mcu.IPNb=2\n\
+        PA9.Signal=USART1_TX\n\
+        ProjectManager.ProjectName=demo\n\
+        RCC.HSE_VALUE=8000000\n\
+        USART1.BaudRate=115200\n";
+
+    fn changes(old: &str, new: &str) -> BTreeMap<IocGroup, Vec<IocChange>> {
+        diff(&IocFile::parse(old), &IocFile::parse(new))
+    }
+
+    fn change(name: &str, old: Option<&str>, new: Option<&str>) -> Vec<IocChange> {
+        vec![IocChange {
+            name: name.to_string(),
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        }]
+    }
+
+    #[test]
+    fn classifies_keys() {
+        let group = |key: &str| classify(key).0;
+        assert_eq!(
+            classify("PA9.Signal"),
+            (IocGroup::Pin("PA9".to_string()), "Signal".to_string())
+        );
+        assert_eq!(
+            group("PC14-OSC32_IN.Mode"),
+            IocGroup::Pin("PC14-OSC32_IN".to_string())
+        );
+        // 虚拟引脚归入对应 IP，组内名称保留完整的键
+        assert_eq!(
+            classify("VP_SYS_VS_tim7.Mode"),
+            (
+                IocGroup::Peripheral("SYS".to_string()),
+                "VP_SYS_VS_tim7.Mode".to_string()
+            )
+        );
+        assert_eq!(group("VP_FREERTOS_VS_CMSIS_V2.Mode"), IocGroup::Rtos);
+        assert_eq!(
+            group("USART1.BaudRate"),
+            IocGroup::Peripheral("USART1".to_string())
+        );
+        assert_eq!(group("RCC.HSE_VALUE"), IocGroup::Clock);
+        assert_eq!(group("FREERTOS.Tasks01"), IocGroup::Rtos);
+        assert_eq!(group("ProjectManager.ProjectName"), IocGroup::Project);
+        assert_eq!(group("Mcu.Family"), IocGroup::Mcu);
+        assert_eq!(group("board"), IocGroup::Mcu);
+    }
+
+    #[test]
+    fn groups_changes() {
+        let new = OLD
+            .replace("USART1_TX", "USART2_TX")
+            .replace("defaultTask,24", "defaultTask,32")
+            .replace("=8000000", "=25000000")
+            .replace("=demo", "=app")
+            .replace("Mcu.IPNb=2\n", "Mcu.IP2=SPI1\nMcu.IPNb=3\n")
+            .replace("USART1.BaudRate=115200\n", "")
+            + "PB3.Signal=SPI1_SCK\n";
+        let groups = changes(OLD, &new);
+        assert_eq!(
+            groups.keys().map(|g| g.to_string()).collect::<Vec<_>>(),
+            [
+                "MCU",
+                "Project",
+                "Clock",
+                "RTOS",
+                "Peripheral USART1",
+                "Pin PA9",
+                "Pin PB3"
+            ]
+        );
+        assert_eq!(groups[&IocGroup::Mcu], change("IP", None, Some("SPI1")));
+        assert_eq!(
+            groups[&IocGroup::Clock],
+            change("HSE_VALUE", Some("8000000"), Some("25000000"))
+        );
+        assert_eq!(
+            groups[&IocGroup::Peripheral("USART1".to_string())],
+            change("BaudRate", Some("115200"), None)
+        );
+        assert_eq!(
+            groups[&IocGroup::Pin("PB3".to_string())],
+            change("Signal", None, Some("SPI1_SCK"))
+        );
+    }
+
+    #[test]
+    fn ignores_timestamp_and_comments() {
+        let new = OLD.replace("Fri Oct 17 10:00:00", "Sat Oct 18 09:30:00") + "# edited by hand\n";
+        assert!(changes(OLD, &new).is_empty());
+    }
+
+    #[test]
+    fn ignores_reordering() {
+        // 键顺序不同，编号列表重新编号
+        let new = "RCC.HSE_VALUE=8000000\n\
+            USART1.BaudRate=115200\n\
+            Mcu.IP0=USART1\n\
+            Mcu.IP1=RCC\n\
+            Mcu.IPNb=2\n\
+            PA9.Signal=USART1_TX\n\
+            ProjectManager.ProjectName=demo\n\
+            FREERTOS.Tasks01=defaultTask,24,128\n";
+        assert!(changes(OLD, new).is_empty());
+    }
+}
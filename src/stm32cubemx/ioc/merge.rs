@@ -0,0 +1,260 @@
+use crate::stm32cubemx::ioc::{INDEXED_LISTS, IocFile, IocLine, Line, is_indexed_list_key};
+use anyhow::{Result, anyhow};
+use std::collections::BTreeSet;
+use tracing::{error, info};
+
+/// 值为逗号分隔参数名列表的键后缀，两边都修改时按集合合并
+const LIST_SUFFIXES: [&str; 3] = [
+    ".IPParameters",
+    ".IPParametersWithoutCheck",
+    ".GPIOParameters",
+];
+
+/// 三方合并的结果
+pub struct MergeResult {
+    pub merged: IocFile,
+    /// 发生冲突的键
+    pub conflicts: Vec<String>,
+}
+
+/// 对列表做三方合并：保留双方的新增，应用双方的删除
+fn merge_list(base: &[String], ours: &[String], theirs: &[String]) -> Vec<String> {
+    let mut result: Vec<String> = ours
+        .iter()
+        .filter(|x| !base.contains(x) || theirs.contains(x))
+        .cloned()
+        .collect();
+    for x in theirs {
+        if !ours.contains(x) && !base.contains(x) && !result.contains(x) {
+            result.push(x.clone());
+        }
+    }
+    result
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// 以 `ours` 为基础，按键做三方合并
+pub fn merge(base: &IocFile, ours: &IocFile, theirs: &IocFile) -> MergeResult {
+    let mut merged = ours.clone();
+    let mut conflicts = Vec::new();
+
+    let keys: BTreeSet<&str> = base
+        .entries()
+        .chain(ours.entries())
+        .chain(theirs.entries())
+        .map(|(k, _)| k)
+        .filter(|k| !is_indexed_list_key(k))
+        .collect();
+
+    for key in keys {
+        let b = base.get_raw(key);
+        let o = ours.get_raw(key);
+        let t = theirs.get_raw(key);
+        if o == t || t == b {
+            continue;
+        }
+        if o == b {
+            match t {
+                Some(t) => merged.set_raw(key, t),
+                None => {
+                    merged.remove(key);
+                }
+            }
+            continue;
+        }
+        if let (Some(o), Some(t)) = (o, t)
+            && LIST_SUFFIXES.iter().any(|suffix| key.ends_with(suffix))
+        {
+            let list = merge_list(&split_list(b.unwrap_or("")), &split_list(o), &split_list(t));
+            merged.set_raw(key, &list.join(","));
+            continue;
+        }
+        mark_conflict(&mut merged, key, o, t);
+        conflicts.push(key.to_string());
+    }
+
+    for (prefix, count_key) in INDEXED_LISTS {
+        let list = merge_list(
+            &base.indexed_list(prefix),
+            &ours.indexed_list(prefix),
+            &theirs.indexed_list(prefix),
+        );
+        if list != ours.indexed_list(prefix) {
+            merged.set_indexed_list(prefix, count_key, &list);
+        }
+    }
+
+    MergeResult { merged, conflicts }
+}
+
+/// 用 git 风格的冲突标记替换该键所在的行
+fn mark_conflict(ioc: &mut IocFile, key: &str, ours: Option<&str>, theirs: Option<&str>) {
+    let mut block = vec!["<<<<<<< ours".to_string()];
+    block.extend(ours.map(|v| format!("{key}={v}")));
+    block.push("=======".to_string());
+    block.extend(theirs.map(|v| format!("{key}={v}")));
+    block.push(">>>>>>> theirs".to_string());
+
+    let eol = ioc.default_eol();
+    let lines = block.into_iter().map(|text| IocLine {
+        line: Line::Raw(text),
+        eol: eol.clone(),
+    });
+    let range = match ioc.position(key) {
+        Some(idx) => idx..idx + 1,
+        None => {
+            let idx = ioc.sorted_position(key);
+            idx..idx
+        }
+    };
+    ioc.lines.splice(range, lines);
+}
+
+/// git merge driver 入口：`stm32tool ioc merge %O %A %B`，结果写回 `%A`
+pub fn run_merge(base: &str, ours: &str, theirs: &str) -> Result<()> {
+    let result = merge(
+        &IocFile::load(base)?,
+        &IocFile::load(ours)?,
+        &IocFile::load(theirs)?,
+    );
+    result.merged.save(ours)?;
+    if result.conflicts.is_empty() {
+        info!("Merged {} without conflicts", ours);
+        return Ok(());
+    }
+    for key in &result.conflicts {
+        error!("Conflict: {}", key);
+    }
+    Err(anyhow!(
+        "{} conflicting key(s) in {}",
+        result.conflicts.len(),
+        ours
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "#MicroXplorer Configuration settings - do not modify\n\
+        File.Version=6\n\
+        Mcu.IP0=NVIC\n\
+        Mcu.IP1=RCC\n\
+        Mcu.IP2=SYS\n\
+        Mcu.IPNb=3\n\
+        Mcu.Pin0=PA13\n\
+        Mcu.Pin1=PA14\n\
+        Mcu.PinsNb=2\n\
+        RCC.HSE_VALUE=8000000\n\
+        RCC.IPParameters=HSE_VALUE,SYSCLKFreq_VALUE\n\
+        RCC.SYSCLKFreq_VALUE=168000000\n\
+        TIM2.Period=999\n";
+
+    fn run(ours: &str, theirs: &str) -> (String, Vec<String>) {
+        let result = merge(
+            &IocFile::parse(BASE),
+            &IocFile::parse(ours),
+            &IocFile::parse(theirs),
+        );
+        (result.merged.to_string(), result.conflicts)
+    }
+
+    #[test]
+    fn takes_one_sided_changes() {
+        let changed = BASE.replace("HSE_VALUE=8000000", "HSE_VALUE=25000000");
+        assert_eq!(run(BASE, &changed), (changed.clone(), vec![]));
+        assert_eq!(run(&changed, BASE), (changed.clone(), vec![]));
+        assert_eq!(run(&changed, &changed), (changed, vec![]));
+
+        let removed = BASE.replace("TIM2.Period=999\n", "");
+        assert_eq!(run(BASE, &removed), (removed, vec![]));
+    }
+
+    #[test]
+    fn marks_conflicting_keys() {
+        let ours = BASE.replace("HSE_VALUE=8000000", "HSE_VALUE=12000000");
+        let theirs = BASE.replace("HSE_VALUE=8000000", "HSE_VALUE=25000000");
+        let (merged, conflicts) = run(&ours, &theirs);
+        assert_eq!(conflicts, ["RCC.HSE_VALUE"]);
+        assert_eq!(
+            merged,
+            BASE.replace(
+                "RCC.HSE_VALUE=8000000\n",
+                "<<<<<<< ours\n\
+                 RCC.HSE_VALUE=12000000\n\
+                 =======\n\
+                 RCC.HSE_VALUE=25000000\n\
+                 >>>>>>> theirs\n"
+            )
+        );
+    }
+
+    #[test]
+    fn unions_parameter_lists() {
+        let ours = BASE.replace("SYSCLKFreq_VALUE\n", "SYSCLKFreq_VALUE,PLLM\n");
+        let theirs = BASE.replace("HSE_VALUE,SYSCLKFreq_VALUE\n", "SYSCLKFreq_VALUE,PLLN\n");
+        let (merged, conflicts) = run(&ours, &theirs);
+        assert!(conflicts.is_empty());
+        // 双方的新增都保留，theirs 删除的 HSE_VALUE 被删除
+        assert!(merged.contains("RCC.IPParameters=SYSCLKFreq_VALUE,PLLM,PLLN\n"));
+    }
+
+    #[test]
+    fn renumbers_indexed_lists() {
+        let ours = BASE
+            .replace("Mcu.IPNb=3\n", "Mcu.IP3=USART1\nMcu.IPNb=4\n")
+            .replace("Mcu.PinsNb=2\n", "Mcu.Pin2=PA9\nMcu.PinsNb=3\n");
+        let theirs = BASE
+            .replace(
+                "Mcu.IP0=NVIC\nMcu.IP1=RCC\nMcu.IP2=SYS\nMcu.IPNb=3\n",
+                "Mcu.IP0=RCC\nMcu.IP1=SYS\nMcu.IP2=SPI1\nMcu.IPNb=3\n",
+            )
+            .replace("Mcu.PinsNb=2\n", "Mcu.Pin2=PB3\nMcu.PinsNb=3\n");
+        let (merged, conflicts) = run(&ours, &theirs);
+        assert!(conflicts.is_empty());
+        let merged = IocFile::parse(&merged);
+        assert_eq!(
+            merged.indexed_list("Mcu.IP"),
+            ["RCC", "SYS", "USART1", "SPI1"]
+        );
+        assert_eq!(merged.get("Mcu.IPNb").as_deref(), Some("4"));
+        assert_eq!(merged.get("Mcu.IP4"), None);
+        assert_eq!(
+            merged.indexed_list("Mcu.Pin"),
+            ["PA13", "PA14", "PA9", "PB3"]
+        );
+        assert_eq!(merged.get("Mcu.PinsNb").as_deref(), Some("4"));
+    }
+
+    #[test]
+    fn conflicts_on_delete_and_change() {
+        let deleted = BASE.replace("TIM2.Period=999\n", "");
+        let changed = BASE.replace("TIM2.Period=999", "TIM2.Period=499");
+
+        let (merged, conflicts) = run(&deleted, &changed);
+        assert_eq!(conflicts, ["TIM2.Period"]);
+        assert!(merged.ends_with(
+            "RCC.SYSCLKFreq_VALUE=168000000\n\
+             <<<<<<< ours\n\
+             =======\n\
+             TIM2.Period=499\n\
+             >>>>>>> theirs\n"
+        ));
+
+        let (merged, conflicts) = run(&changed, &deleted);
+        assert_eq!(conflicts, ["TIM2.Period"]);
+        assert!(merged.ends_with(
+            "<<<<<<< ours\n\
+             TIM2.Period=499\n\
+             =======\n\
+             >>>>>>> theirs\n"
+        ));
+    }
+}
@@ -0,0 +1,561 @@
+//! STM32CubeMX `.ioc` 文件的读写
+//!
+//! `.ioc` 是 Java properties 格式的 `key=value` 文件。这里按行保存原始内容，
+//! 未修改的行原样写回，因此没有编辑时输出与输入逐字节一致。
+
+use crate::dry_run;
+use crate::stm32cubemx::{Toolchain, get_toolchain};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+pub mod diff;
+pub mod merge;
+pub mod normalize;
+
+/// 编号列表（`Mcu.IP0` …）的前缀及其计数键。列表的编号没有实际含义，
+/// 比较和合并时按集合处理
+pub const INDEXED_LISTS: [(&str, &str); 2] = [("Mcu.IP", "Mcu.IPNb"), ("Mcu.Pin", "Mcu.PinsNb")];
+
+/// 判断是否为编号列表中的键
+pub fn is_indexed_list_key(key: &str) -> bool {
+    INDEXED_LISTS.iter().any(|(prefix, count_key)| {
+        key == *count_key
+            || key
+                .strip_prefix(prefix)
+                .is_some_and(|idx| idx.parse::<usize>().is_ok())
+    })
+}
+
+/// `.ioc` 中的一行
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// 注释（`#` 或 `!` 开头）或空行，原样保存
+    Raw(String),
+    /// `key=value` 条目，`raw` 为原始文本，条目被修改后置为 `None`
+    Entry {
+        key: String,
+        value: String,
+        raw: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IocLine {
+    line: Line,
+    /// 行尾换行符（`\n`、`\r\n` 或最后一行的空串）
+    eol: String,
+}
+
+/// 解析后的 `.ioc` 文件，保留键顺序和注释
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IocFile {
+    lines: Vec<IocLine>,
+}
+
+/// `Mcu.*` 中描述芯片的字段
+#[derive(Debug, Clone, Default)]
+pub struct McuInfo {
+    /// `Mcu.Name`，如 `STM32F407V(E-G)Tx`
+    pub name: Option<String>,
+    /// `Mcu.UserName`，如 `STM32F407VETx`
+    pub user_name: Option<String>,
+    /// `Mcu.Family`，如 `STM32F4`
+    pub family: Option<String>,
+    /// `Mcu.Package`，如 `LQFP100`
+    pub package: Option<String>,
+    /// `Mcu.CPN`，如 `STM32F407VET6`
+    pub cpn: Option<String>,
+}
+
+/// 一个引脚上的配置（`PA9.Signal`、`PA9.Mode`、`PA9.GPIO_Label`）
+#[derive(Debug, Clone, Default)]
+pub struct PinAssignment {
+    /// 引脚名，如 `PA9` 或 `PH0-OSC_IN`
+    pub pin: String,
+    pub signal: Option<String>,
+    pub mode: Option<String>,
+    pub label: Option<String>,
+}
+
+/// `ProjectManager.*` 中常用的字段
+#[derive(Debug, Clone, Default)]
+pub struct ProjectSettings {
+    pub project_name: Option<String>,
+    pub target_toolchain: Option<String>,
+    pub firmware_package: Option<String>,
+    pub under_root: bool,
+    pub couple_file: bool,
+}
+
+impl IocFile {
+    /// 解析 `.ioc` 文本
+    pub fn parse(content: &str) -> IocFile {
+        let mut lines = Vec::new();
+        for chunk in content.split_inclusive('\n') {
+            let (text, eol) = if let Some(text) = chunk.strip_suffix("\r\n") {
+                (text, "\r\n")
+            } else if let Some(text) = chunk.strip_suffix('\n') {
+                (text, "\n")
+            } else {
+                (chunk, "")
+            };
+            lines.push(IocLine {
+                line: parse_line(text),
+                eol: eol.to_string(),
+            });
+        }
+        IocFile { lines }
+    }
+
+    /// 读取并解析 `.ioc` 文件
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<IocFile> {
+        let path = path.as_ref();
+        let content = dry_run::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(IocFile::parse(&content))
+    }
+
+    /// 写回 `.ioc` 文件
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        dry_run::write(path, &self.to_string())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// 按原顺序遍历所有条目，值为未转义的原始文本
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|l| match &l.line {
+            Line::Entry { key, value, .. } => Some((key.as_str(), value.as_str())),
+            Line::Raw(_) => None,
+        })
+    }
+
+    /// 获取未转义的原始值（如 `C\:\\path`）
+    pub fn get_raw(&self, key: &str) -> Option<&str> {
+        self.entries().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// 获取转义还原后的值
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.get_raw(key).map(unescape_value)
+    }
+
+    /// 设置值，值会按 properties 规则转义。键不存在时追加到文件末尾
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.set_raw(key, &escape_value(value));
+    }
+
+    /// 设置未转义的原始值。值未变化时保持原始行不变；
+    /// 键不存在时按键名插入到合适位置（CubeMX 按键名排序保存）
+    pub fn set_raw(&mut self, key: &str, value: &str) {
+        for l in self.lines.iter_mut() {
+            if let Line::Entry {
+                key: k,
+                value: v,
+                raw,
+            } = &mut l.line
+                && k == key
+            {
+                if v != value {
+                    *v = value.to_string();
+                    *raw = None;
+                }
+                return;
+            }
+        }
+        self.insert_sorted(
+            key,
+            Line::Entry {
+                key: key.to_string(),
+                value: value.to_string(),
+                raw: None,
+            },
+        );
+    }
+
+    /// 删除条目，返回被删除的原始值
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let idx = self.position(key)?;
+        match self.lines.remove(idx).line {
+            Line::Entry { value, .. } => Some(value),
+            Line::Raw(_) => None,
+        }
+    }
+
+    /// 只保留满足条件的条目，注释不受影响
+    pub fn retain<F: FnMut(&str, &str) -> bool>(&mut self, mut f: F) {
+        self.lines.retain(|l| match &l.line {
+            Line::Entry { key, value, .. } => f(key, value),
+            Line::Raw(_) => true,
+        });
+    }
+
+    /// 读取 `Mcu.IP0`、`Mcu.IP1` … 这类编号列表，按编号排序
+    pub fn indexed_list(&self, prefix: &str) -> Vec<String> {
+        let mut items: Vec<(usize, String)> = self
+            .entries()
+            .filter_map(|(k, v)| {
+                let idx = k.strip_prefix(prefix)?.parse::<usize>().ok()?;
+                Some((idx, unescape_value(v)))
+            })
+            .collect();
+        items.sort_by_key(|(idx, _)| *idx);
+        items.into_iter().map(|(_, v)| v).collect()
+    }
+
+    /// 重写编号列表及其计数键（如 `Mcu.IPNb`），新列表从 0 开始编号
+    pub fn set_indexed_list(&mut self, prefix: &str, count_key: &str, items: &[String]) {
+        self.retain(|k, _| {
+            k.strip_prefix(prefix)
+                .and_then(|idx| idx.parse::<usize>().ok())
+                .is_none_or(|idx| idx < items.len())
+        });
+        for (idx, item) in items.iter().enumerate() {
+            self.set(&format!("{prefix}{idx}"), item);
+        }
+        self.set(count_key, &items.len().to_string());
+    }
+
+    /// 芯片信息
+    pub fn mcu(&self) -> McuInfo {
+        McuInfo {
+            name: self.get("Mcu.Name"),
+            user_name: self.get("Mcu.UserName"),
+            family: self.get("Mcu.Family"),
+            package: self.get("Mcu.Package"),
+            cpn: self.get("Mcu.CPN"),
+        }
+    }
+
+    /// 工程设置
+    pub fn project(&self) -> ProjectSettings {
+        ProjectSettings {
+            project_name: self.get("ProjectManager.ProjectName"),
+            target_toolchain: self.get("ProjectManager.TargetToolchain"),
+            firmware_package: self.get("ProjectManager.FirmwarePackage"),
+            under_root: self.get("ProjectManager.UnderRoot").as_deref() == Some("true"),
+            couple_file: self.get("ProjectManager.CoupleFile").as_deref() == Some("true"),
+        }
+    }
+
+    /// 工程使用的工具链
+    pub fn toolchain(&self) -> Option<Toolchain> {
+        let name = self.get("ProjectManager.TargetToolchain")?;
+        Toolchain::value_variants()
+            .iter()
+            .find(|t| get_toolchain(t) == name)
+            .copied()
+    }
+
+    /// 外部高速晶振频率（Hz）
+    pub fn hse_value(&self) -> Option<u64> {
+        self.get("RCC.HSE_VALUE")?.parse().ok()
+    }
+
+    /// 系统时钟频率（Hz）
+    pub fn sysclk(&self) -> Option<u64> {
+        self.get("RCC.SYSCLKFreq_VALUE")?.parse().ok()
+    }
+
+    /// 某个 IP 的全部参数，如 `ip_parameters("USART1")`
+    pub fn ip_parameters(&self, ip: &str) -> BTreeMap<String, String> {
+        let prefix = format!("{ip}.");
+        self.entries()
+            .filter_map(|(k, v)| Some((k.strip_prefix(&prefix)?.to_string(), unescape_value(v))))
+            .collect()
+    }
+
+    /// 所有引脚配置，按引脚名排序
+    pub fn pins(&self) -> Vec<PinAssignment> {
+        let mut pins: BTreeMap<String, PinAssignment> = BTreeMap::new();
+        for (k, v) in self.entries() {
+            let Some((pin, param)) = k.split_once('.') else {
+                continue;
+            };
+            if !is_pin_name(pin) {
+                continue;
+            }
+            let entry = pins
+                .entry(pin.to_string())
+                .or_insert_with(|| PinAssignment {
+                    pin: pin.to_string(),
+                    ..Default::default()
+                });
+            let value = Some(unescape_value(v));
+            match param {
+                "Signal" => entry.signal = value,
+                "Mode" => entry.mode = value,
+                "GPIO_Label" => entry.label = value,
+                _ => {}
+            }
+        }
+        pins.into_values().collect()
+    }
+
+    /// 条目所在的行号
+    fn position(&self, key: &str) -> Option<usize> {
+        self.lines
+            .iter()
+            .position(|l| matches!(&l.line, Line::Entry { key: k, .. } if k == key))
+    }
+
+    /// 按键名排序时 `key` 应插入的位置：第一个键名大于它的条目处，没有则为末尾。
+    /// 插入末尾时补全最后一行的换行符
+    fn sorted_position(&mut self, key: &str) -> usize {
+        let idx = self
+            .lines
+            .iter()
+            .position(|l| matches!(&l.line, Line::Entry { key: k, .. } if k.as_str() > key));
+        match idx {
+            Some(idx) => idx,
+            None => {
+                let eol = self.default_eol();
+                if let Some(last) = self.lines.last_mut()
+                    && last.eol.is_empty()
+                {
+                    last.eol = eol;
+                }
+                self.lines.len()
+            }
+        }
+    }
+
+    fn insert_sorted(&mut self, key: &str, line: Line) {
+        let eol = self.default_eol();
+        let idx = self.sorted_position(key);
+        self.lines.insert(idx, IocLine { line, eol });
+    }
+
+    /// 新增条目使用的换行符，与文件已有换行符一致
+    fn default_eol(&self) -> String {
+        self.lines
+            .iter()
+            .map(|l| l.eol.as_str())
+            .find(|eol| !eol.is_empty())
+            .unwrap_or("\n")
+            .to_string()
+    }
+}
+
+impl fmt::Display for IocFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for l in &self.lines {
+            match &l.line {
+                Line::Raw(text) => f.write_str(text)?,
+                Line::Entry { raw: Some(raw), .. } => f.write_str(raw)?,
+                Line::Entry {
+                    key,
+                    value,
+                    raw: None,
+                } => write!(f, "{}={value}", escape_value(key))?,
+            }
+            f.write_str(&l.eol)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_line(text: &str) -> Line {
+    let trimmed = text.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('!') {
+        return Line::Raw(text.to_string());
+    }
+    // 第一个未转义的 `=` 或 `:` 为分隔符
+    let mut escaped = false;
+    let mut split = None;
+    for (i, c) in trimmed.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '=' || c == ':' {
+            split = Some(i);
+            break;
+        }
+    }
+    let (key, value) = match split {
+        Some(i) => (&trimmed[..i], &trimmed[i + 1..]),
+        None => (trimmed, ""),
+    };
+    Line::Entry {
+        key: unescape_value(key.trim_end()),
+        value: value.trim_start().to_string(),
+        raw: Some(text.to_string()),
+    }
+}
+
+/// 判断是否为引脚名，如 `PA9`、`PH0-OSC_IN`、`PC14-OSC32_IN`
+pub fn is_pin_name(name: &str) -> bool {
+    let base = name.split('-').next().unwrap_or(name);
+    let mut chars = base.chars();
+    chars.next() == Some('P') && chars.next().is_some_and(|c| c.is_ascii_uppercase()) && {
+        let rest: String = chars.collect();
+        !rest.is_empty() && rest.len() <= 2 && rest.chars().all(|c| c.is_ascii_digit())
+    }
+}
+
+/// 还原 properties 转义（`\:`、`\=`、`\\` 等）
+pub fn unescape_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    result
+}
+
+/// 按 CubeMX 的写法转义值
+pub fn escape_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            ':' => result.push_str("\\:"),
+            '=' => result.push_str("\\="),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// 打印 .ioc 文件概要，未指定文件时使用当前目录下唯一的 .ioc 文件
+pub fn print_info(file: Option<String>) -> Result<()> {
+    let file = match file {
+        Some(file) => file,
+        None => crate::stm32cubemx::find_ioc_file()?,
+    };
+    let ioc = IocFile::load(&file)?;
+
+    let mcu = ioc.mcu();
+    let project = ioc.project();
+    let unknown = || "-".to_string();
+    println!("File:       {}", file);
+    println!(
+        "MCU:        {} ({})",
+        mcu.user_name.unwrap_or_else(unknown),
+        mcu.name.unwrap_or_else(unknown)
+    );
+    println!("Family:     {}", mcu.family.unwrap_or_else(unknown));
+    println!("Package:    {}", mcu.package.unwrap_or_else(unknown));
+    println!("CPN:        {}", mcu.cpn.unwrap_or_else(unknown));
+    println!(
+        "Project:    {}",
+        project.project_name.unwrap_or_else(unknown)
+    );
+    println!(
+        "Toolchain:  {}",
+        project.target_toolchain.unwrap_or_else(unknown)
+    );
+    println!(
+        "Firmware:   {}",
+        project.firmware_package.unwrap_or_else(unknown)
+    );
+    println!(
+        "Options:    under root: {}, couple files by IP: {}",
+        project.under_root, project.couple_file
+    );
+    let freq = |v: Option<u64>| v.map_or_else(unknown, |v| format!("{} Hz", v));
+    println!("HSE:        {}", freq(ioc.hse_value()));
+    println!("SYSCLK:     {}", freq(ioc.sysclk()));
+
+    println!();
+    println!("Peripherals:");
+    for ip in ioc.indexed_list("Mcu.IP") {
+        println!("  {}", ip);
+        for (name, value) in ioc.ip_parameters(&ip) {
+            println!("    {} = {}", name, value);
+        }
+    }
+
+    println!();
+    println!("Pins:");
+    for pin in ioc.pins() {
+        println!(
+            "  {:<16} {:<24} {}",
+            pin.pin,
+            pin.signal.as_deref().unwrap_or("-"),
+            pin.label.as_deref().unwrap_or("")
+        );
+        if let Some(mode) = pin.mode {
+            println!("  {:<16} mode: {}", "", mode);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "#MicroXplorer Configuration settings - do not modify\r\n\
+        ! legacy comment\r\n\
+        \r\n\
+        File.Version=6\r\n\
+        Mcu.Family=STM32F4\r\n\
+        ProjectManager.ProjectFileName=demo.ioc\r\n\
+        ProjectManager.ProjectPath=C\\:\\\\work\\\\demo\r\n\
+        RCC.HSE_VALUE = 8000000\r\n\
+        VP_SYS\\:Tick\\=TIM6.Mode=TIM6\r\n\
+        board=custom";
+
+    #[test]
+    fn round_trip_is_byte_identical() {
+        let ioc = IocFile::parse(SAMPLE);
+        assert_eq!(ioc.to_string(), SAMPLE);
+        let lf = SAMPLE.replace("\r\n", "\n");
+        assert_eq!(IocFile::parse(&lf).to_string(), lf);
+        assert_eq!(IocFile::parse("").to_string(), "");
+    }
+
+    #[test]
+    fn parses_escaped_keys_and_values() {
+        let ioc = IocFile::parse(SAMPLE);
+        assert_eq!(ioc.get("VP_SYS:Tick=TIM6.Mode").as_deref(), Some("TIM6"));
+        assert_eq!(
+            ioc.get("ProjectManager.ProjectPath").as_deref(),
+            Some("C:\\work\\demo")
+        );
+        assert_eq!(ioc.get("RCC.HSE_VALUE").as_deref(), Some("8000000"));
+        assert_eq!(ioc.mcu().family.as_deref(), Some("STM32F4"));
+    }
+
+    #[test]
+    fn edits_only_touch_changed_lines() {
+        let mut ioc = IocFile::parse(SAMPLE);
+        ioc.set("RCC.HSE_VALUE", "8000000");
+        assert_eq!(ioc.to_string(), SAMPLE);
+
+        ioc.set("RCC.HSE_VALUE", "25000000");
+        ioc.set("VP_SYS:Tick=TIM6.Mode", "TIM7");
+        ioc.set("Mcu.Name", "STM32F407V(E-G)Tx");
+        let expected = SAMPLE
+            .replace("RCC.HSE_VALUE = 8000000", "RCC.HSE_VALUE=25000000")
+            .replace("=TIM6.Mode=TIM6", "=TIM6.Mode=TIM7")
+            .replace(
+                "Mcu.Family=STM32F4\r\n",
+                "Mcu.Family=STM32F4\r\nMcu.Name=STM32F407V(E-G)Tx\r\n",
+            );
+        assert_eq!(ioc.to_string(), expected);
+        let reparsed = IocFile::parse(&expected);
+        assert_eq!(
+            reparsed.get("VP_SYS:Tick=TIM6.Mode").as_deref(),
+            Some("TIM7")
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use crate::stm32cubemx::find_ioc_file;
+use crate::stm32cubemx::ioc::{IocFile, IocLine, Line, escape_value, unescape_value};
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// CubeMX 每次保存都会写入的时间戳注释，如 `#Tue Oct 07 23:03:40 CST 2025`
+const TIMESTAMP_PATTERN: &str = r"^#\w{3} \w{3} \d{1,2} \d{2}:\d{2}:\d{2} [\w+:-]+ \d{4}$";
+
+/// 把路径统一为 `/` 分隔，便于跨平台比较
+fn slash_path(path: &str) -> String {
+    path.replace('\\', "/").trim_end_matches('/').to_string()
+}
+
+/// 判断是否为绝对路径，同时识别 Windows 盘符路径
+fn is_absolute_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    path.starts_with('/')
+        || (bytes.len() > 2
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && (bytes[2] == b'/' || bytes[2] == b'\\'))
+}
+
+/// 把位于工程目录下的绝对路径改写为相对路径
+fn relativize(value: &str, project_dir: &str) -> Option<String> {
+    if !is_absolute_path(value) {
+        return None;
+    }
+    let value = slash_path(value);
+    let rest = if cfg!(target_os = "windows") {
+        let prefix = value.get(..project_dir.len())?;
+        if !prefix.eq_ignore_ascii_case(project_dir) {
+            return None;
+        }
+        &value[project_dir.len()..]
+    } else {
+        value.strip_prefix(project_dir)?
+    };
+    match rest.strip_prefix('/') {
+        Some(rest) => Some(rest.to_string()),
+        None if rest.is_empty() => Some(".".to_string()),
+        None => None,
+    }
+}
+
+/// 规范化 .ioc：删除时间戳注释，按键名排序，`ProjectManager.*` 中的绝对路径改为相对路径。
+/// 返回内容是否发生变化
+pub fn normalize(ioc: &mut IocFile, project_dir: &Path) -> bool {
+    let before = ioc.to_string();
+    let timestamp = Regex::new(TIMESTAMP_PATTERN).unwrap();
+    let project_dir = slash_path(&project_dir.to_string_lossy());
+
+    for (key, value) in ioc
+        .entries()
+        .filter(|(k, _)| k.starts_with("ProjectManager."))
+        .filter_map(|(k, v)| Some((k.to_string(), relativize(&unescape_value(v), &project_dir)?)))
+        .collect::<Vec<_>>()
+    {
+        ioc.set_raw(&key, &escape_value(&value));
+    }
+
+    let eol = ioc.default_eol();
+    let mut comments = Vec::new();
+    let mut entries = Vec::new();
+    for l in ioc.lines.drain(..) {
+        match l.line {
+            Line::Raw(ref text) if text.trim().is_empty() || timestamp.is_match(text.trim()) => {}
+            Line::Raw(_) => comments.push(IocLine {
+                line: l.line,
+                eol: eol.clone(),
+            }),
+            Line::Entry { .. } => entries.push(IocLine {
+                line: l.line,
+                eol: eol.clone(),
+            }),
+        }
+    }
+    // 稳定排序，与 CubeMX 保存时的顺序一致
+    entries.sort_by(|a, b| match (&a.line, &b.line) {
+        (Line::Entry { key: a, .. }, Line::Entry { key: b, .. }) => a.cmp(b),
+        _ => std::cmp::Ordering::Equal,
+    });
+    ioc.lines = comments.into_iter().chain(entries).collect();
+
+    ioc.to_string() != before
+}
+
+/// .ioc 文件所在的目录。不用 `canonicalize`，它在 Windows 上返回 `\\?\C:\...`，
+/// 无法与 .ioc 中的 `C:/...` 匹配
+fn project_dir(file: &str) -> Result<PathBuf> {
+    Ok(std::path::absolute(file)?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default())
+}
+
+/// 规范化 .ioc 文件并写回，返回内容是否发生变化
+pub fn normalize_file(file: &str) -> Result<bool> {
+    let mut ioc = IocFile::load(file)?;
+    let changed = normalize(&mut ioc, &project_dir(file)?);
+    if changed {
+        ioc.save(file)?;
+        info!("Normalized {}", file);
+    }
+    Ok(changed)
+}
+
+/// `ioc normalize` 入口。`check` 为真时只检查，不写回
+pub fn run_normalize(file: Option<String>, check: bool) -> Result<()> {
+    let file = match file {
+        Some(file) => file,
+        None => find_ioc_file()?,
+    };
+    if check {
+        let mut ioc = IocFile::load(&file)?;
+        if normalize(&mut ioc, &project_dir(&file)?) {
+            return Err(anyhow!("{} is not normalized", file));
+        }
+        info!("{} is normalized", file);
+    } else if !normalize_file(&file)? {
+        info!("{} is already normalized", file);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "#MicroXplorer Configuration settings - do not modify\r\n\
+        #Tue Oct 07 23:03:40 CST 2025\r\n\
+        RCC.HSE_VALUE=8000000\r\n\
+        \r\n\
+        Mcu.IP1=USART1\r\n\
+        Mcu.IP0=RCC\r\n\
+        ProjectManager.ProjectFileName=demo.ioc\r\n\
+        ProjectManager.ProjectPath=C\\:\\\\work\\\\demo\r\n\
+        ProjectManager.TargetToolchain=CMake\r\n\
+        File.Version=6\r\n";
+
+    fn normalized(content: &str, project_dir: &str) -> (String, bool) {
+        let mut ioc = IocFile::parse(content);
+        let changed = normalize(&mut ioc, Path::new(project_dir));
+        (ioc.to_string(), changed)
+    }
+
+    #[test]
+    fn strips_timestamp_and_sorts_keys() {
+        let (content, changed) = normalized(SAMPLE, "C:\\work\\demo");
+        assert!(changed);
+        assert_eq!(
+            content,
+            "#MicroXplorer Configuration settings - do not modify\r\n\
+             File.Version=6\r\n\
+             Mcu.IP0=RCC\r\n\
+             Mcu.IP1=USART1\r\n\
+             ProjectManager.ProjectFileName=demo.ioc\r\n\
+             ProjectManager.ProjectPath=.\r\n\
+             ProjectManager.TargetToolchain=CMake\r\n\
+             RCC.HSE_VALUE=8000000\r\n"
+        );
+    }
+
+    #[test]
+    fn is_idempotent() {
+        let (once, _) = normalized(SAMPLE, "C:\\work\\demo");
+        let (twice, changed) = normalized(&once, "C:\\work\\demo");
+        assert!(!changed);
+        assert_eq!(twice, once);
+    }
+
+    #[test]
+    fn sort_ignores_input_order() {
+        // 打乱输入顺序，结果相同
+        let mut lines: Vec<&str> = SAMPLE.split_inclusive("\r\n").collect();
+        lines[2..].reverse();
+        let (shuffled, _) = normalized(&lines.concat(), "C:\\work\\demo");
+        assert_eq!(shuffled, normalized(SAMPLE, "C:\\work\\demo").0);
+    }
+
+    #[test]
+    fn relativizes_project_paths() {
+        assert_eq!(
+            relativize("C:\\work\\demo\\Core", "C:/work/demo").as_deref(),
+            Some("Core")
+        );
+        assert_eq!(
+            relativize("C:/work/demo/", "C:/work/demo").as_deref(),
+            Some(".")
+        );
+        assert_eq!(
+            relativize("/home/me/demo/build", "/home/me/demo").as_deref(),
+            Some("build")
+        );
+        // 工程目录外、前缀相同的兄弟目录和相对路径保持不变
+        assert_eq!(relativize("/home/me/demo2", "/home/me/demo"), None);
+        assert_eq!(relativize("D:/other", "C:/work/demo"), None);
+        assert_eq!(relativize("Core/Src", "/home/me/demo"), None);
+
+        let posix = "ProjectManager.ProjectPath=/home/me/demo\n\
+            ProjectManager.UnderRoot=false\n";
+        assert_eq!(
+            normalized(posix, "/home/me/demo").0,
+            "ProjectManager.ProjectPath=.\nProjectManager.UnderRoot=false\n"
+        );
+        // 其他 IP 中的路径不做修改
+        let other = "USB.Path=/home/me/demo/usb\n";
+        assert_eq!(
+            normalized(other, "/home/me/demo"),
+            (other.to_string(), false)
+        );
+    }
+}
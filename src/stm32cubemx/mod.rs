@@ -0,0 +1,125 @@
+use crate::dry_run;
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::cmp::PartialEq;
+use std::fs;
+use tracing::{info, warn};
+
+pub mod backend;
+pub mod discovery;
+pub mod ioc;
+pub mod output;
+pub mod process;
+pub mod script;
+
+use script::CubeMxScript;
+
+fn get_ioc_files() -> Vec<String> {
+    let mut ioc_files: Vec<String> = Vec::new();
+    let current_dir = dry_run::current_dir().expect("Failed to get current directory");
+    if let Ok(entries) = fs::read_dir(current_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if let Some(extension) = path.extension()
+                && extension == "ioc"
+            {
+                ioc_files.push(path.to_str().unwrap().to_string());
+            }
+        }
+    }
+    ioc_files
+}
+
+/// 获取当前目录下唯一的 .ioc 文件
+pub fn find_ioc_file() -> Result<String> {
+    let mut ioc_files = get_ioc_files();
+    if ioc_files.len() != 1 {
+        warn!("No ioc file is provided or multiple ioc files are provided.");
+        return Err(anyhow::anyhow!(
+            "No ioc file is provided or multiple ioc files are provided."
+        ));
+    }
+    Ok(ioc_files.remove(0))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Toolchain {
+    /// EWARM V8.32
+    EwarmV832,
+    /// EWARM V8
+    EwarmV800,
+    /// EWARM V7
+    EwarmV700,
+    /// MDK-ARM V5.32
+    MdmArmV532,
+    /// MDK-ARM V5.27
+    MdmArmV527,
+    /// MDK-ARM V5
+    MdmArmV500,
+    /// MDK-ARM V4
+    MdmArmV400,
+    /// STM32CubeIDE
+    #[value(name = "stm32cubeide")]
+    #[serde(rename = "stm32cubeide")]
+    STM32CubeIDE,
+    /// Makefile
+    #[value(name = "makefile")]
+    #[serde(rename = "makefile")]
+    Makefile,
+    /// CMake
+    #[value(name = "cmake")]
+    #[serde(rename = "cmake")]
+    CMake,
+}
+
+pub fn get_toolchain(toolchain: &Toolchain) -> &'static str {
+    match toolchain {
+        Toolchain::EwarmV832 => "EWARM V8.32",
+        Toolchain::EwarmV800 => "EWARM V8",
+        Toolchain::EwarmV700 => "EWARM V7",
+        Toolchain::MdmArmV532 => "MDK-ARM V5.32",
+        Toolchain::MdmArmV527 => "MDK-ARM V5.27",
+        Toolchain::MdmArmV500 => "MDK-ARM V5",
+        Toolchain::MdmArmV400 => "MDK-ARM V4",
+        Toolchain::STM32CubeIDE => "STM32CubeIDE",
+        Toolchain::Makefile => "Makefile",
+        Toolchain::CMake => "CMake",
+    }
+}
+
+pub fn generate_code(toolchain: Option<Toolchain>) -> Result<()> {
+    let ioc_file = find_ioc_file()?;
+    let mut script = CubeMxScript::new().config_load(&ioc_file);
+    if let Some(toolchain) = toolchain {
+        script = script.project_toolchain(get_toolchain(&toolchain));
+        if let Toolchain::STM32CubeIDE = toolchain {
+            // Generate Under Root on
+            script = script.project_generate_under_root(true);
+        }
+    }
+    // Generate peripheral initialization as a pair of '.c/.h' files per peripheral
+    let script = script
+        .project_couple_files_by_ip(true)
+        .project_generate()
+        .exit();
+
+    run_script(&script)
+}
+
+/// 用当前的 CubeMX 后端执行脚本
+pub fn run_script(script: &CubeMxScript) -> Result<()> {
+    script.validate()?;
+
+    if dry_run::enabled() {
+        info!("[dry-run] Would run STM32CubeMX script:");
+        println!("{}", script);
+        return Ok(());
+    }
+
+    // 同一工程同时只允许一个 CubeMX 运行
+    let _lock = process::ProjectLock::acquire()?;
+
+    backend::current().run(script)
+}
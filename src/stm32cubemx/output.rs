@@ -0,0 +1,147 @@
+use anyhow::Result;
+use chrono::Local;
+use regex::Regex;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// CubeMX 输出日志所在目录
+pub const LOG_DIR: &str = ".stm32tool/logs";
+
+/// 最多保留的日志文件数，超出时删除最旧的
+const MAX_LOGS: usize = 20;
+
+/// CubeMX 报错的行：`[ERROR]`、`SEVERE`、`KO` 等级，或 Java 异常及其堆栈头
+const ERROR_LINE: &str = r"(?i)^\s*(\[?(error|severe|fatal)\]?\b|ko\b|exception in thread\b|caused by:|([\w$]+\.)+[\w$]*exception\b)|\[(error|severe|fatal)\]|\b(error|severe|fatal|ko)\s*:";
+
+/// 已知的 CubeMX 错误和对应的提示，只在报错的行中匹配
+const DIAGNOSTICS: [(&str, &str); 5] = [
+    (
+        r"(?i)unknown command|command not (found|recognized)|not a valid command",
+        "CubeMX did not recognize a script command, check the script shown in the log",
+    ),
+    (
+        r"(?i)licen[cs]e.*(not (been )?accepted|must (first )?(be )?accept|agreement)",
+        "CubeMX license is not accepted, open STM32CubeMX once and accept the license",
+    ),
+    (
+        r"(?i)(firmware package|\bpack\b).*(not (found|available|installed)|missing)|no firmware package",
+        "The firmware package for this MCU is missing, install it in STM32CubeMX (Help > Manage embedded software packages)",
+    ),
+    (
+        r"(?i)(cannot|can't|unable to|failed to|error while) (load|open|read).*\.ioc|\.ioc.*(not found|does not exist)",
+        "CubeMX failed to load the .ioc file, check the path and that it was saved by a compatible CubeMX version",
+    ),
+    (
+        r"^\s*(Exception in thread\b|Caused by:|([\w$]+\.)+[\w$]*Exception\b)",
+        "CubeMX threw an exception, see the log for the stack trace",
+    ),
+];
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// 设置是否实时输出 CubeMX 的 stdout/stderr
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+/// 逐行读取子进程输出，存入 `lines`，`--verbose` 时同时打印
+pub fn capture<R: Read + Send + 'static>(
+    reader: R,
+    lines: Arc<Mutex<Vec<String>>>,
+) -> JoinHandle<()> {
+    let verbose = VERBOSE.load(Ordering::Relaxed);
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(|l| l.ok()) {
+            if verbose {
+                eprintln!("[cubemx] {}", line);
+            }
+            lines.lock().unwrap().push(line);
+        }
+    })
+}
+
+/// 把脚本和 CubeMX 输出写入 `.stm32tool/logs/cubemx-<时间>.log`
+pub fn write_log(script: &str, lines: &[String]) -> Result<PathBuf> {
+    fs::create_dir_all(LOG_DIR)?;
+    let path = PathBuf::from(LOG_DIR).join(format!(
+        "cubemx-{}.log",
+        Local::now().format("%Y%m%d-%H%M%S%.3f")
+    ));
+    let content = format!("# script\n{}\n\n# output\n{}\n", script, lines.join("\n"));
+    fs::write(&path, content)?;
+    prune_logs();
+    Ok(path)
+}
+
+/// 只保留最新的 `MAX_LOGS` 个日志，文件名中的时间保证按名称排序即按时间排序
+fn prune_logs() {
+    let Ok(entries) = fs::read_dir(LOG_DIR) else {
+        return;
+    };
+    let mut logs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("cubemx-") && n.ends_with(".log"))
+        })
+        .collect();
+    logs.sort();
+    let excess = logs.len().saturating_sub(MAX_LOGS);
+    for old in &logs[..excess] {
+        let _ = fs::remove_file(old);
+    }
+}
+
+/// 在报错的行中查找已知错误，返回去重后的提示
+pub fn diagnose(lines: &[String]) -> Vec<&'static str> {
+    let error_line = Regex::new(ERROR_LINE).unwrap();
+    let errors: Vec<&String> = lines.iter().filter(|l| error_line.is_match(l)).collect();
+    DIAGNOSTICS
+        .iter()
+        .filter(|(pattern, _)| {
+            let pattern = Regex::new(pattern).unwrap();
+            errors.iter().any(|l| pattern.is_match(l))
+        })
+        .map(|(_, message)| *message)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn ignores_normal_output() {
+        let output = lines(
+            "Loading license agreement information\n\
+             Checking for STM32CubeMX updates, ExceptionHandler registered\n\
+             The firmware package STM32Cube_FW_F4 is installed\n\
+             OK",
+        );
+        assert!(diagnose(&output).is_empty());
+    }
+
+    #[test]
+    fn reports_error_lines() {
+        let output = lines(
+            "[ERROR] Unknown command: projet generate\n\
+             SEVERE: License agreement not accepted\n\
+             java.lang.NullPointerException: null\n\
+             \tat com.st.microxplorer.Main.run(Main.java:42)",
+        );
+        assert_eq!(
+            diagnose(&output),
+            vec![DIAGNOSTICS[0].1, DIAGNOSTICS[1].1, DIAGNOSTICS[4].1]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{Result, anyhow};
+use rand::distr::Alphanumeric;
+use rand::{Rng, rng};
+use std::fs::{self, OpenOptions, remove_file};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{env, process};
+use tracing::{info, warn};
+
+/// 防止同一工程同时运行多个 CubeMX 的锁文件
+const LOCK_FILE: &str = ".stm32tool/cubemx.lock";
+
+/// CubeMX 运行超时（秒），0 表示不限制
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(600);
+/// 是否正在运行 CubeMX
+static RUNNING: AtomicBool = AtomicBool::new(false);
+/// 运行期间是否收到了 Ctrl-C
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_timeout(secs: u64) {
+    TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+}
+
+/// 注册 Ctrl-C 处理：运行 CubeMX 时终止其进程树并清理临时文件，否则直接退出
+pub fn install_ctrlc_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if RUNNING.load(Ordering::SeqCst) {
+            CANCELLED.store(true, Ordering::SeqCst);
+        } else {
+            process::exit(130);
+        }
+    })?;
+    Ok(())
+}
+
+fn generate_random_string(length: usize) -> String {
+    let mut rng = rng();
+    (0..length)
+        .map(|_| rng.sample(Alphanumeric))
+        .map(char::from)
+        .collect()
+}
+
+/// 系统临时目录下的脚本文件，离开作用域时删除
+pub struct TempScript {
+    path: PathBuf,
+}
+
+impl TempScript {
+    pub fn create(content: &str) -> Result<TempScript> {
+        let path = env::temp_dir().join(format!("tmp-script-{}", generate_random_string(8)));
+        fs::write(&path, content)?;
+        Ok(TempScript { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempScript {
+    fn drop(&mut self) {
+        let _ = remove_file(&self.path);
+    }
+}
+
+/// 进程是否仍在运行
+fn process_alive(pid: u32) -> bool {
+    if cfg!(target_os = "windows") {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+            .unwrap_or(false)
+    } else {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false)
+    }
+}
+
+/// 工程锁，持有期间其他 stm32tool 进程不能在此工程中运行 CubeMX，离开作用域时释放
+pub struct ProjectLock;
+
+impl ProjectLock {
+    pub fn acquire() -> Result<ProjectLock> {
+        if let Some(parent) = Path::new(LOCK_FILE).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        for _ in 0..2 {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(LOCK_FILE)
+            {
+                Ok(mut file) => {
+                    write!(file, "{}", process::id())?;
+                    return Ok(ProjectLock);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let pid = fs::read_to_string(LOCK_FILE)
+                        .ok()
+                        .and_then(|s| s.trim().parse::<u32>().ok());
+                    match pid {
+                        Some(pid) if process_alive(pid) => {
+                            return Err(anyhow!(
+                                "CubeMX is already running in this project (pid {}), remove {} if this is wrong",
+                                pid,
+                                LOCK_FILE
+                            ));
+                        }
+                        _ => {
+                            warn!("Removing stale lock {}", LOCK_FILE);
+                            remove_file(LOCK_FILE)?;
+                        }
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(anyhow!("Failed to acquire {}", LOCK_FILE))
+    }
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        let _ = remove_file(LOCK_FILE);
+    }
+}
+
+/// 在独立的进程组中启动，便于超时或取消时终止整个进程树
+pub fn spawn(command: &mut Command) -> std::io::Result<Child> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let child = command.spawn()?;
+    CANCELLED.store(false, Ordering::SeqCst);
+    RUNNING.store(true, Ordering::SeqCst);
+    Ok(child)
+}
+
+/// 终止子进程及其创建的所有进程（如 STM32CubeMX 启动的 java）
+fn kill_tree(child: &mut Child) {
+    let pid = child.id().to_string();
+    let killed = if cfg!(target_os = "windows") {
+        Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+    } else {
+        Command::new("kill")
+            .args(["-KILL", "--", &format!("-{}", pid)])
+            .stderr(Stdio::null())
+            .status()
+    };
+    if !killed.is_ok_and(|s| s.success()) {
+        let _ = child.kill();
+    }
+    let _ = child.wait();
+}
+
+/// 等待子进程结束，超时或收到 Ctrl-C 时终止进程树并返回错误
+pub fn wait(child: &mut Child) -> Result<ExitStatus> {
+    let timeout = TIMEOUT_SECS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let result = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) => {}
+            Err(e) => break Err(e.into()),
+        }
+        if CANCELLED.load(Ordering::SeqCst) {
+            info!("Interrupted, stopping STM32CubeMX");
+            kill_tree(child);
+            break Err(anyhow!("STM32CubeMX was interrupted"));
+        }
+        if timeout > 0 && start.elapsed() >= Duration::from_secs(timeout) {
+            kill_tree(child);
+            break Err(anyhow!(
+                "STM32CubeMX did not finish within {} seconds and was stopped",
+                timeout
+            ));
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    RUNNING.store(false, Ordering::SeqCst);
+    result
+}
@@ -0,0 +1,398 @@
+use anyhow::{Result, anyhow};
+use std::fmt;
+
+/// 一条 CubeMX 脚本命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptCommand {
+    /// `# ...`
+    Comment(String),
+    /// `load <mcu>`，以芯片新建工程
+    Load { mcu: String },
+    /// `config load <file>`
+    ConfigLoad { file: String },
+    /// `config saveas <file>`
+    ConfigSaveAs { file: String },
+    /// `set mode <ip> <mode>`
+    SetMode { ip: String, mode: String },
+    /// `set pin <pin> <signal>`
+    SetPin { pin: String, signal: String },
+    /// `set ip parameters <ip> <name> <value>`
+    SetIpParameters {
+        ip: String,
+        name: String,
+        value: String,
+    },
+    /// `set gpio parameters <pin> <name> <value>`
+    SetGpioParameters {
+        pin: String,
+        name: String,
+        value: String,
+    },
+    /// `clock set <name> <value>`
+    ClockSet { name: String, value: String },
+    /// `mmt load pre-config <name>`
+    MmtLoadPreConfig { name: String },
+    /// `project toolchain <toolchain>`
+    ProjectToolchain { toolchain: String },
+    /// `project couplefilesbyip <0|1>`
+    ProjectCoupleFilesByIp(bool),
+    /// `project generateunderroot <0|1>`
+    ProjectGenerateUnderRoot(bool),
+    /// `project generate`
+    ProjectGenerate,
+    /// `SetStructure <structure>`
+    SetStructure { structure: String },
+    /// `SetCopyLibrary <mode>`
+    SetCopyLibrary { mode: String },
+    /// `exit`
+    Exit,
+    /// 未建模的其他命令（如 `project name`、`set dma`），原样传给 CubeMX
+    Raw(String),
+}
+
+/// 参数包含空白或为空时加双引号。CubeMX 不支持转义，参数中不能出现双引号
+fn quote(arg: &str) -> String {
+    if arg.is_empty() || arg.chars().any(char::is_whitespace) {
+        format!("\"{}\"", arg)
+    } else {
+        arg.to_string()
+    }
+}
+
+fn flag(value: bool) -> &'static str {
+    if value { "1" } else { "0" }
+}
+
+impl fmt::Display for ScriptCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ScriptCommand::*;
+        match self {
+            Comment(text) => write!(f, "# {}", text),
+            Load { mcu } => write!(f, "load {}", quote(mcu)),
+            ConfigLoad { file } => write!(f, "config load {}", quote(file)),
+            ConfigSaveAs { file } => write!(f, "config saveas {}", quote(file)),
+            SetMode { ip, mode } => write!(f, "set mode {} {}", quote(ip), quote(mode)),
+            SetPin { pin, signal } => write!(f, "set pin {} {}", quote(pin), quote(signal)),
+            SetIpParameters { ip, name, value } => write!(
+                f,
+                "set ip parameters {} {} {}",
+                quote(ip),
+                quote(name),
+                quote(value)
+            ),
+            SetGpioParameters { pin, name, value } => write!(
+                f,
+                "set gpio parameters {} {} {}",
+                quote(pin),
+                quote(name),
+                quote(value)
+            ),
+            ClockSet { name, value } => write!(f, "clock set {} {}", quote(name), quote(value)),
+            MmtLoadPreConfig { name } => write!(f, "mmt load pre-config {}", quote(name)),
+            ProjectToolchain { toolchain } => write!(f, "project toolchain {}", quote(toolchain)),
+            ProjectCoupleFilesByIp(on) => write!(f, "project couplefilesbyip {}", flag(*on)),
+            ProjectGenerateUnderRoot(on) => write!(f, "project generateunderroot {}", flag(*on)),
+            ProjectGenerate => write!(f, "project generate"),
+            SetStructure { structure } => write!(f, "SetStructure {}", quote(structure)),
+            SetCopyLibrary { mode } => write!(f, "SetCopyLibrary {}", quote(mode)),
+            Exit => write!(f, "exit"),
+            Raw(line) => write!(f, "{}", line),
+        }
+    }
+}
+
+/// 按空白切分一行，双引号内的空白不切分
+fn tokenize(line: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut token = String::new();
+        if c == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => token.push(c),
+                    None => return Err(anyhow!("unterminated quote")),
+                }
+            }
+            if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                return Err(anyhow!("missing space after closing quote"));
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                if c == '"' {
+                    return Err(anyhow!("unexpected quote in {}", token));
+                }
+                token.push(c);
+                chars.next();
+            }
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// 已建模的命令，参数个数不对时报错而不是原样传递
+const KNOWN_COMMANDS: &[&[&str]] = &[
+    &["load"],
+    &["config", "load"],
+    &["config", "saveas"],
+    &["set", "mode"],
+    &["set", "pin"],
+    &["set", "ip", "parameters"],
+    &["set", "gpio", "parameters"],
+    &["clock", "set"],
+    &["mmt", "load", "pre-config"],
+    &["project", "toolchain"],
+    &["project", "couplefilesbyip"],
+    &["project", "generateunderroot"],
+    &["project", "generate"],
+    &["SetStructure"],
+    &["SetCopyLibrary"],
+    &["exit"],
+];
+
+fn parse_flag(value: &str) -> Result<bool> {
+    match value {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(anyhow!("expected 0 or 1, got {}", value)),
+    }
+}
+
+impl ScriptCommand {
+    /// 解析一行脚本，空行返回 `None`
+    fn parse(line: &str) -> Result<Option<ScriptCommand>> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        if let Some(text) = line.strip_prefix('#') {
+            return Ok(Some(ScriptCommand::Comment(text.trim().to_string())));
+        }
+        let tokens = tokenize(line)?;
+        let words: Vec<&str> = tokens.iter().map(String::as_str).collect();
+        let arg = |s: &str| s.to_string();
+        let command = match words.as_slice() {
+            ["load", mcu] => ScriptCommand::Load { mcu: arg(mcu) },
+            ["config", "load", file] => ScriptCommand::ConfigLoad { file: arg(file) },
+            ["config", "saveas", file] => ScriptCommand::ConfigSaveAs { file: arg(file) },
+            ["set", "mode", ip, mode] => ScriptCommand::SetMode {
+                ip: arg(ip),
+                mode: arg(mode),
+            },
+            ["set", "pin", pin, signal] => ScriptCommand::SetPin {
+                pin: arg(pin),
+                signal: arg(signal),
+            },
+            ["set", "ip", "parameters", ip, name, value] => ScriptCommand::SetIpParameters {
+                ip: arg(ip),
+                name: arg(name),
+                value: arg(value),
+            },
+            ["set", "gpio", "parameters", pin, name, value] => ScriptCommand::SetGpioParameters {
+                pin: arg(pin),
+                name: arg(name),
+                value: arg(value),
+            },
+            ["clock", "set", name, value] => ScriptCommand::ClockSet {
+                name: arg(name),
+                value: arg(value),
+            },
+            ["mmt", "load", "pre-config", name] => {
+                ScriptCommand::MmtLoadPreConfig { name: arg(name) }
+            }
+            ["project", "toolchain", toolchain] => ScriptCommand::ProjectToolchain {
+                toolchain: arg(toolchain),
+            },
+            ["project", "couplefilesbyip", on] => {
+                ScriptCommand::ProjectCoupleFilesByIp(parse_flag(on)?)
+            }
+            ["project", "generateunderroot", on] => {
+                ScriptCommand::ProjectGenerateUnderRoot(parse_flag(on)?)
+            }
+            ["project", "generate"] => ScriptCommand::ProjectGenerate,
+            ["SetStructure", structure] => ScriptCommand::SetStructure {
+                structure: arg(structure),
+            },
+            ["SetCopyLibrary", mode] => ScriptCommand::SetCopyLibrary { mode: arg(mode) },
+            ["exit"] => ScriptCommand::Exit,
+            _ if KNOWN_COMMANDS.iter().any(|head| words.starts_with(head)) => {
+                return Err(anyhow!("wrong arguments: {}", line));
+            }
+            _ => ScriptCommand::Raw(line.to_string()),
+        };
+        Ok(Some(command))
+    }
+}
+
+impl ScriptCommand {
+    /// 对每个参数应用 `f`，未建模的命令和注释对整行应用
+    fn map_args(&self, f: &impl Fn(&str) -> String) -> ScriptCommand {
+        use ScriptCommand::*;
+        match self {
+            Comment(text) => Comment(f(text)),
+            Load { mcu } => Load { mcu: f(mcu) },
+            ConfigLoad { file } => ConfigLoad { file: f(file) },
+            ConfigSaveAs { file } => ConfigSaveAs { file: f(file) },
+            SetMode { ip, mode } => SetMode {
+                ip: f(ip),
+                mode: f(mode),
+            },
+            SetPin { pin, signal } => SetPin {
+                pin: f(pin),
+                signal: f(signal),
+            },
+            SetIpParameters { ip, name, value } => SetIpParameters {
+                ip: f(ip),
+                name: f(name),
+                value: f(value),
+            },
+            SetGpioParameters { pin, name, value } => SetGpioParameters {
+                pin: f(pin),
+                name: f(name),
+                value: f(value),
+            },
+            ClockSet { name, value } => ClockSet {
+                name: f(name),
+                value: f(value),
+            },
+            MmtLoadPreConfig { name } => MmtLoadPreConfig { name: f(name) },
+            ProjectToolchain { toolchain } => ProjectToolchain {
+                toolchain: f(toolchain),
+            },
+            SetStructure { structure } => SetStructure {
+                structure: f(structure),
+            },
+            SetCopyLibrary { mode } => SetCopyLibrary { mode: f(mode) },
+            Raw(line) => Raw(f(line)),
+            ProjectCoupleFilesByIp(_) | ProjectGenerateUnderRoot(_) | ProjectGenerate | Exit => {
+                self.clone()
+            }
+        }
+    }
+}
+
+/// CubeMX 脚本，按命令逐条构建，渲染时统一处理引号
+#[derive(Debug, Clone, Default)]
+pub struct CubeMxScript {
+    commands: Vec<ScriptCommand>,
+}
+
+impl CubeMxScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 解析脚本文本，已知命令参数有误时报告行号，其他命令原样保留
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut script = Self::new();
+        for (idx, line) in text.lines().enumerate() {
+            let command =
+                ScriptCommand::parse(line).map_err(|e| anyhow!("line {}: {}", idx + 1, e))?;
+            if let Some(command) = command {
+                script.commands.push(command);
+            }
+        }
+        Ok(script)
+    }
+
+    pub fn command(mut self, command: ScriptCommand) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    pub fn config_load(self, file: &str) -> Self {
+        self.command(ScriptCommand::ConfigLoad {
+            file: file.to_string(),
+        })
+    }
+
+    pub fn project_toolchain(self, toolchain: &str) -> Self {
+        self.command(ScriptCommand::ProjectToolchain {
+            toolchain: toolchain.to_string(),
+        })
+    }
+
+    pub fn project_generate_under_root(self, on: bool) -> Self {
+        self.command(ScriptCommand::ProjectGenerateUnderRoot(on))
+    }
+
+    pub fn project_couple_files_by_ip(self, on: bool) -> Self {
+        self.command(ScriptCommand::ProjectCoupleFilesByIp(on))
+    }
+
+    pub fn project_generate(self) -> Self {
+        self.command(ScriptCommand::ProjectGenerate)
+    }
+
+    pub fn exit(self) -> Self {
+        self.command(ScriptCommand::Exit)
+    }
+
+    /// 去掉注释后的脚本
+    pub fn without_comments(&self) -> Self {
+        Self {
+            commands: self
+                .commands
+                .iter()
+                .filter(|c| !matches!(c, ScriptCommand::Comment(_)))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// 对每条命令的参数应用 `f`，在渲染（加引号）之前替换参数内容
+    pub fn map_args(&self, f: impl Fn(&str) -> String) -> Self {
+        Self {
+            commands: self.commands.iter().map(|c| c.map_args(&f)).collect(),
+        }
+    }
+
+    /// 检查每条命令渲染后能被原样解析回来，即参数中没有双引号、换行等 CubeMX 无法表示的内容
+    pub fn validate(&self) -> Result<()> {
+        for command in &self.commands {
+            let text = command.to_string();
+            match ScriptCommand::parse(&text) {
+                Ok(Some(parsed)) if parsed == *command => {}
+                _ => return Err(anyhow!("invalid argument in command: {}", text)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CubeMxScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.commands.iter().map(|c| c.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_unmodelled_commands() {
+        let text = "load STM32F407VETx\nproject name demo\nset dma USART1_RX DMA2_Stream2\nexit";
+        let script = CubeMxScript::parse(text).unwrap();
+        script.validate().unwrap();
+        assert_eq!(script.to_string(), text);
+    }
+
+    #[test]
+    fn rejects_known_commands_with_wrong_arguments() {
+        let err = CubeMxScript::parse("load STM32F407VETx\nset mode USART1").unwrap_err();
+        assert!(err.to_string().starts_with("line 2:"));
+        assert!(CubeMxScript::parse("project generate now").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::env;
+use std::path::PathBuf;
 use std::process::Command;
 
 pub fn get_author() -> String {
@@ -10,3 +12,23 @@ pub fn get_author() -> String {
         .trim()
         .to_string()
 }
+
+pub fn get_email() -> String {
+    Command::new("git")
+        .args(["config", "user.email"])
+        .output()
+        .ok()
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// 用户配置目录，优先使用环境变量 `STM32TOOL_CONFIG_DIR`，
+/// 默认为系统配置目录下的 `stm32tool`（Linux 下为 `~/.config/stm32tool`）
+pub fn user_config_dir() -> Option<PathBuf> {
+    if let Ok(dir) = env::var("STM32TOOL_CONFIG_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    dirs::config_dir().map(|dir| dir.join("stm32tool"))
+}
@@ -0,0 +1,228 @@
+use crate::config;
+use crate::initializers::{self, FPUType, IdeInitializer};
+use crate::patches::{Patch, apply_patch, describe, get_file, is_applied};
+use crate::pipeline;
+use crate::project::{PROJECT_FILE, ProjectConfig};
+use crate::render::InitContext;
+use crate::stm32cubemx::find_ioc_file;
+use crate::stm32cubemx::ioc::IocFile;
+use anyhow::anyhow;
+use tracing::{info, warn};
+
+/// 缺失的一项
+enum Missing {
+    /// 用户代码目录或文件
+    UserCode(&'static str),
+    /// 补丁未应用，`fix` 为补回时应用的补丁
+    Patch { what: String, fix: Vec<Patch> },
+    /// 补丁的目标文件不存在，无法自动修复
+    File(String),
+}
+
+impl Missing {
+    fn describe(&self) -> String {
+        match self {
+            Missing::UserCode(path) => format!("{} (UserCode layout)", path),
+            Missing::Patch { what, .. } => what.clone(),
+            Missing::File(file) => format!("{} not found", file),
+        }
+    }
+}
+
+/// 要检查的初始化器和选项
+struct Target {
+    chosen: Vec<usize>,
+    /// 补回 FPU 配置时使用的浮点类型，`None` 时不检查 FPU
+    fpu: Option<FPUType>,
+    non_intrusive_headers: bool,
+}
+
+/// 依次使用 `--ide`、项目配置、profile、.ioc 中的工具链确定要检查的初始化器
+fn select(
+    ides: &[Box<dyn IdeInitializer>],
+    names: &[String],
+    fpu: Option<FPUType>,
+) -> anyhow::Result<Target> {
+    let recorded = ProjectConfig::load().init.or(&config::profile().init);
+    let chosen = if !names.is_empty() {
+        initializers::find(ides, names)?
+    } else if let Some(recorded) = &recorded.ides {
+        initializers::find(ides, recorded)?
+    } else {
+        let toolchain = find_ioc_file()
+            .and_then(IocFile::load)
+            .ok()
+            .and_then(|ioc| ioc.toolchain());
+        ides.iter()
+            .enumerate()
+            .filter(|(_, ide)| toolchain.is_some() && ide.toolchain() == toolchain)
+            .map(|(idx, _)| idx)
+            .collect()
+    };
+    Ok(Target {
+        chosen,
+        fpu: Some(fpu.or(recorded.fpu).unwrap_or(FPUType::Hard)),
+        non_intrusive_headers: recorded.non_intrusive_headers(),
+    })
+}
+
+/// 检查一组补丁，返回缺失项
+fn check_patches(patches: Vec<Patch>, missing: &mut Vec<Missing>) {
+    for patch in patches {
+        match is_applied(&patch) {
+            Some(true) => {}
+            Some(false) => missing.push(Missing::Patch {
+                what: describe(&patch),
+                fix: vec![patch],
+            }),
+            None => missing.push(Missing::File(get_file(&patch).to_string())),
+        }
+    }
+}
+
+/// 收集未应用的补丁
+fn collect_patches(ides: &[Box<dyn IdeInitializer>], target: &Target) -> Vec<Missing> {
+    let pipeline = pipeline::current();
+    let mut missing = Vec::new();
+    for &idx in &target.chosen {
+        let id = ides[idx].id();
+        check_patches(pipeline.patches(id).to_vec(), &mut missing);
+
+        // 硬件或软件浮点任一配置存在即可，补回时使用 `target.fpu`
+        let Some(fpu) = target.fpu else {
+            continue;
+        };
+        let fpu_patches = [FPUType::Hard, FPUType::Soft].map(|f| pipeline.fpu_patches(id, f));
+        let configured = fpu_patches
+            .iter()
+            .any(|patches| patches.iter().all(|p| is_applied(p) == Some(true)));
+        let file_exists = fpu_patches[0]
+            .first()
+            .is_some_and(|p| is_applied(p).is_some());
+        if !configured && file_exists {
+            missing.push(Missing::Patch {
+                what: format!("{}: FPU block", get_file(&fpu_patches[0][0])),
+                fix: pipeline.fpu_patches(id, fpu),
+            });
+        }
+    }
+
+    // 非侵入式头文件只检查项目中存在的构建文件
+    if target.non_intrusive_headers {
+        for patch in &pipeline.headers {
+            if is_applied(patch) == Some(false) {
+                missing.push(Missing::Patch {
+                    what: describe(patch),
+                    fix: vec![patch.clone()],
+                });
+            }
+        }
+    }
+    missing
+}
+
+/// 收集当前项目缺失的内容
+fn collect(ides: &[Box<dyn IdeInitializer>], target: &Target) -> Vec<Missing> {
+    pipeline::current()
+        .missing_user_code()
+        .into_iter()
+        .map(Missing::UserCode)
+        .chain(collect_patches(ides, target))
+        .collect()
+}
+
+/// 应用缺失项中的补丁
+fn apply_fixes(missing: &[Missing]) -> anyhow::Result<()> {
+    for item in missing {
+        if let Missing::Patch { fix, .. } = item {
+            for patch in fix {
+                apply_patch(patch)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// generate 之后按项目配置中记录的 init 选择重新应用补丁，已应用的保持不变。
+/// 没有记录时不做任何修改，避免改动未用 stm32tool 初始化的项目
+pub fn reapply() -> anyhow::Result<()> {
+    let recorded = ProjectConfig::load().init;
+    let Some(names) = &recorded.ides else {
+        info!(
+            "No init recorded in {}, skip re-applying init patches",
+            PROJECT_FILE
+        );
+        return Ok(());
+    };
+    let ides = initializers::all();
+    let target = Target {
+        chosen: initializers::find(&ides, names)?,
+        fpu: recorded.fpu,
+        non_intrusive_headers: recorded.skip_generate_user_code == Some(false)
+            && recorded.skip_non_intrusive_headers == Some(false),
+    };
+    let is_patch = |m: &Missing| matches!(m, Missing::Patch { .. });
+    let missing: Vec<Missing> = collect_patches(&ides, &target)
+        .into_iter()
+        .filter(is_patch)
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    apply_fixes(&missing)?;
+    let failed: Vec<Missing> = collect_patches(&ides, &target)
+        .into_iter()
+        .filter(is_patch)
+        .collect();
+    info!(
+        "Re-applied {} init patch(es) lost during generation",
+        missing.len() - failed.len()
+    );
+    for item in &failed {
+        warn!("Failed to re-apply {}", item.describe());
+    }
+    Ok(())
+}
+
+/// 检查 init 的修改是否仍然存在，`fix` 时补回缺失的部分
+pub fn run_verify(names: &[String], fpu: Option<FPUType>, fix: bool) -> anyhow::Result<()> {
+    let ides = initializers::all();
+    let target = select(&ides, names, fpu)?;
+    if target.chosen.is_empty() {
+        warn!("No IDE matches the project toolchain, use --ide to choose one");
+    }
+    for &idx in &target.chosen {
+        info!("Checking {}", ides[idx].name());
+    }
+
+    let mut missing = collect(&ides, &target);
+    if fix && !missing.is_empty() {
+        if missing.iter().any(|m| matches!(m, Missing::UserCode(_))) {
+            pipeline::current().generate_user_code(&InitContext::new(), false)?;
+        }
+        for item in &missing {
+            if matches!(item, Missing::Patch { .. }) {
+                info!("Re-applying {}", item.describe());
+            }
+        }
+        apply_fixes(&missing)?;
+        missing = collect(&ides, &target);
+    }
+
+    for item in &missing {
+        println!("[missing] {}", item.describe());
+    }
+    if missing.is_empty() {
+        info!("All init patches are applied");
+        return Ok(());
+    }
+    Err(anyhow!(
+        "{} item(s) missing{}",
+        missing.len(),
+        if fix {
+            ", the files may have changed too much to patch"
+        } else {
+            ", run `stm32tool verify --fix` to re-apply them"
+        }
+    ))
+}
@@ -0,0 +1,153 @@
+//! 用录制的 CubeMX 结果（`tests/fixtures/cubemx`）跑完整的 create + init，不需要安装 CubeMX
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 每个测试独立的工作目录
+fn work_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("stm32tool-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn stm32tool(dir: &Path) -> Command {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/cubemx");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_stm32tool"));
+    command
+        .current_dir(dir)
+        .env("STM32TOOL_CONFIG_DIR", dir.join("config"))
+        .env(
+            "STM32TOOL_CUBEMX",
+            format!("fixture:{}", fixtures.display()),
+        )
+        .env("GIT_AUTHOR_NAME", "test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com");
+    command
+}
+
+#[test]
+fn create_and_init_from_fixture() {
+    let dir = work_dir("fixture");
+    let output = stm32tool(&dir)
+        .args([
+            "create",
+            "demo",
+            "--mcu",
+            "STM32F407VETx",
+            "--toolchain",
+            "cmake",
+        ])
+        .args([
+            "--run-init",
+            "--ide",
+            "cmake",
+            "--yes",
+            "--no-input",
+            "--strict",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let project = dir.join("demo");
+    let read = |file: &str| fs::read_to_string(project.join(file)).unwrap();
+    // 第一次运行录制的 .ioc，之后按板级默认值修改
+    assert!(read("demo.ioc").contains("RCC.HSE_VALUE=8000000"));
+    // 第二次运行录制的生成代码，init 打上补丁
+    let cmake = read("CMakeLists.txt");
+    assert!(cmake.contains("file(GLOB_RECURSE SOURCES \"UserCode/*.*\")"));
+    assert!(cmake.contains("include_directories(UserCode)"));
+    assert!(cmake.contains("-mfpu=fpv4-sp-d16"));
+    assert!(project.join("Core/Src/main.c").is_file());
+    assert!(project.join("UserCode/app/app.c").is_file());
+    assert!(read("stm32tool.toml").contains("STM32F407VETx"));
+
+    // 再次检查补丁全部生效
+    let output = stm32tool(&project).arg("verify").output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn missing_fixture_fails() {
+    let dir = work_dir("missing-fixture");
+    let output = stm32tool(&dir)
+        .args([
+            "create",
+            "demo",
+            "--mcu",
+            "STM32H723VETx",
+            "--yes",
+            "--no-input",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not found"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn dry_run_create_plans_inside_project() {
+    let dir = work_dir("dry-run");
+    fs::create_dir_all(dir.join("demo")).unwrap();
+    fs::write(dir.join("demo/stm32tool.toml"), "old").unwrap();
+    let output = stm32tool(&dir)
+        .args(["--dry-run", "create", "demo", "--mcu", "STM32F407VETx"])
+        .args(["--run-init", "--ide", "cmake", "--no-input"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+
+    // 已有项目会被删除，计划中说明需要确认，且旧文件不再作为 diff 的基准
+    assert!(stderr.contains("Would ask to regenerate existing project demo"));
+    assert!(stdout.contains("--- /dev/null\n+++ b/demo/stm32tool.toml"));
+    // 文件和 git 命令都位于新项目中
+    assert!(stdout.contains("+++ b/demo/.clang-format"));
+    assert!(stdout.contains("+++ b/demo/UserCode/app/app.h"));
+    assert!(stderr.contains("Would run in demo: git init"));
+    assert!(!stderr.contains("Would create directory \n"));
+    // 磁盘上没有任何变化
+    assert_eq!(
+        fs::read_to_string(dir.join("demo/stm32tool.toml")).unwrap(),
+        "old"
+    );
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    assert_eq!(fs::read_dir(dir.join("demo")).unwrap().count(), 1);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn fixture_replays_in_dir_with_spaces() {
+    // 工程路径中有空格时脚本会加引号，回放仍能找到录制结果
+    let dir = work_dir("with space");
+    let output = stm32tool(&dir)
+        .args(["create", "demo", "--mcu", "STM32F407VETx", "--no-input"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(dir.join("demo/demo.ioc").is_file());
+    assert!(dir.join("demo/CMakeLists.txt").is_file());
+    let _ = fs::remove_dir_all(&dir);
+}
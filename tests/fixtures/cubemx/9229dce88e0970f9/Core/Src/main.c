@@ -0,0 +1,16 @@
+#include "main.h"
+
+int main(void)
+{
+  HAL_Init();
+
+  /* USER CODE BEGIN 2 */
+
+  /* USER CODE END 2 */
+
+  while (1)
+  {
+    /* USER CODE BEGIN 3 */
+    /* USER CODE END 3 */
+  }
+}
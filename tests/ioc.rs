@@ -0,0 +1,168 @@
+//! `ioc` 子命令作为 git merge driver / external diff 时的命令行行为
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// 每个测试独立的工作目录
+fn work_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("stm32tool-ioc-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn command(dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_stm32tool"));
+    command
+        .current_dir(dir)
+        .env("STM32TOOL_CONFIG_DIR", dir.join("config"));
+    command
+}
+
+fn stm32tool(dir: &Path, args: &[&str]) -> Output {
+    command(dir).args(args).output().unwrap()
+}
+
+const BASE: &str = "Mcu.IP0=RCC\nMcu.IPNb=1\nRCC.HSE_VALUE=8000000\nTIM2.Period=999\n";
+
+#[test]
+fn merge_writes_result_to_ours() {
+    let dir = work_dir("merge");
+    fs::write(dir.join("base.ioc"), BASE).unwrap();
+    fs::write(dir.join("ours.ioc"), BASE.replace("999", "499")).unwrap();
+    fs::write(dir.join("theirs.ioc"), BASE.replace("8000000", "25000000")).unwrap();
+
+    let output = stm32tool(
+        &dir,
+        &["ioc", "merge", "base.ioc", "ours.ioc", "theirs.ioc"],
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.join("ours.ioc")).unwrap(),
+        "Mcu.IP0=RCC\nMcu.IPNb=1\nRCC.HSE_VALUE=25000000\nTIM2.Period=499\n"
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn merge_conflict_exits_with_1() {
+    let dir = work_dir("merge-conflict");
+    fs::write(dir.join("base.ioc"), BASE).unwrap();
+    fs::write(dir.join("ours.ioc"), BASE.replace("999", "499")).unwrap();
+    fs::write(dir.join("theirs.ioc"), BASE.replace("999", "1999")).unwrap();
+
+    let output = stm32tool(
+        &dir,
+        &["ioc", "merge", "base.ioc", "ours.ioc", "theirs.ioc"],
+    );
+    // git 依据非零退出码判断合并失败，冲突标记留在 %A 中
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 conflicting key(s)"));
+    assert!(
+        fs::read_to_string(dir.join("ours.ioc")).unwrap().ends_with(
+            "<<<<<<< ours\nTIM2.Period=499\n=======\nTIM2.Period=1999\n>>>>>>> theirs\n"
+        )
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn diff_accepts_git_external_diff_arguments() {
+    let dir = work_dir("diff");
+    fs::write(dir.join("old.ioc"), BASE).unwrap();
+    fs::write(dir.join("new.ioc"), BASE.replace("8000000", "25000000")).unwrap();
+
+    // git diff 调用 external diff 时传入 path old-file old-hex old-mode new-file new-hex new-mode
+    let output = stm32tool(
+        &dir,
+        &[
+            "ioc", "diff", "demo.ioc", "old.ioc", "1111111", "100644", "new.ioc", "2222222",
+            "100644",
+        ],
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "ioc diff a/demo.ioc b/demo.ioc\n[Clock]\n  ~ HSE_VALUE: 8000000 -> 25000000\n"
+    );
+
+    let output = stm32tool(&dir, &["ioc", "diff", "old.ioc", "old.ioc"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = stm32tool(&dir, &["ioc", "diff", "a", "old.ioc", "x", "new.ioc"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("got 4 arguments"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn normalize_check_fails_on_unnormalized_file() {
+    let dir = work_dir("normalize");
+    let file = dir.join("demo.ioc");
+    // .ioc 中的路径按 properties 格式转义
+    let project = dir
+        .to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace(':', "\\:");
+    fs::write(
+        &file,
+        format!(
+            "#Tue Oct 07 23:03:40 CST 2025\nTIM2.Period=999\nProjectManager.ProjectPath={project}\n"
+        ),
+    )
+    .unwrap();
+    let before = fs::read_to_string(&file).unwrap();
+
+    let output = stm32tool(&dir, &["ioc", "normalize", "--check"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not normalized"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), before);
+
+    let output = stm32tool(&dir, &["ioc", "normalize"]);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "ProjectManager.ProjectPath=.\nTIM2.Period=999\n"
+    );
+    let output = stm32tool(&dir, &["ioc", "normalize", "--check"]);
+    assert!(output.status.success());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn stale_profile_and_pipeline_only_affect_init_commands() {
+    let dir = work_dir("stale-config");
+    fs::write(dir.join("base.ioc"), BASE).unwrap();
+    fs::write(dir.join("ours.ioc"), BASE).unwrap();
+    fs::write(dir.join("theirs.ioc"), BASE.replace("999", "499")).unwrap();
+    let run = |args: &[&str]| {
+        command(&dir)
+            .env("STM32TOOL_PROFILE", "missing")
+            .env("STM32TOOL_PIPELINE", "missing.yaml")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    // git 调用 merge driver 时不应因为 profile 或流水线失效而失败
+    let output = run(&["ioc", "merge", "base.ioc", "ours.ioc", "theirs.ioc"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        run(&["ioc", "diff", "base.ioc", "ours.ioc"])
+            .status
+            .success()
+    );
+    assert!(run(&["list", "mcus"]).status.success());
+
+    let output = run(&["init", "--ide", "cmake"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Profile missing not found"));
+    let _ = fs::remove_dir_all(&dir);
+}